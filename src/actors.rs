@@ -1,47 +1,301 @@
-use crate::actors::background::Background;
-use crate::actors::bubble::Bubbles;
-use crate::actors::pipe::Pipes;
-use crate::actors::score::Score;
-use crate::actors::sub::Sub;
+use std::any::Any;
+use sdl2::event::Event;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
 use crate::{GameState, Renderable};
+use crate::events::GameEvent;
+use crate::save::{SaveReader, SaveWriter};
 use crate::theme::Theme;
 
 pub mod background;
 pub mod bubble;
+pub mod current;
+pub mod enemy;
+pub mod fauna;
+pub mod ghost;
+pub mod parallax;
+pub mod pickup;
 pub mod pipe;
 pub mod score;
 pub mod sub;
+pub mod terrain;
+pub mod torpedo;
 
+/// Scenes of the game, actors can be visible in one or more of them
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Scene {
+    /// Title screen, before the game starts
+    Title,
+    /// Actual game
+    Game,
+}
+
+/// How an actor takes part in collision detection
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CollisionTag {
+    /// Does not collide
+    None,
+    /// Crashes into obstacles
+    Player,
+    /// Something players crash into
+    Obstacle,
+    /// Something players can pick up
+    Collectible,
+    /// Fired by players, hits obstacles
+    Projectile,
+}
+
+/// Players passing an obstacle within this many pixels had a near miss
+const NEAR_MISS_MARGIN: i32 = 12;
+
+/// Render layers. Actors with a lower layer are rendered first.
+pub mod layer {
+    pub const BACKGROUND: i32 = 0;
+    pub const FAUNA: i32 = 10;
+    pub const ENEMIES: i32 = 15;
+    pub const OBSTACLES: i32 = 20;
+    pub const PLAYER: i32 = 30;
+    pub const FOREGROUND: i32 = 40;
+    pub const HUD: i32 = 100;
+}
+
+struct ActorEntry<'a> {
+    z: i32,
+    scenes: Vec<Scene>,
+    tag: CollisionTag,
+    actor: Box<dyn Renderable + 'a>,
+}
+
+/// Registry of all the actors in the game. New actors only need to be added here, and are updated, rendered and
+/// checked for collisions automatically.
 pub struct Actors<'a> {
-    pub sub: Sub<'a>,
-    pub bubbles: Bubbles<'a>,
-    pub pipes: Pipes<'a>,
-    pub background: Background<'a>,
-    pub score: Score<'a>
+    entries: Vec<ActorEntry<'a>>,
+    // True while a player is within the near miss margin of an obstacle, so we only report it once
+    near_miss: bool,
 }
 
 impl<'a> Actors<'a> {
+    pub(crate) fn new() -> Self {
+        Self { entries: Vec::new(), near_miss: false }
+    }
+
+    /// Adds an actor on the given layer, visible in the given scenes
+    pub(crate) fn add(&mut self, z: i32, scenes: &[Scene], tag: CollisionTag, actor: Box<dyn Renderable + 'a>) {
+        self.entries.push(ActorEntry {
+            z,
+            scenes: scenes.to_vec(),
+            tag,
+            actor,
+        });
+
+        // Stable sort, so actors on the same layer are rendered in the order they were added
+        self.entries.sort_by_key(|e| e.z);
+    }
+
     pub(crate) fn switch_theme(&mut self, theme: &Theme) {
-        self.background.switch_theme(theme);
-        self.sub.switch_theme(theme);
-        self.bubbles.switch_theme(theme);
-        self.pipes.switch_theme(theme);
-        self.score.switch_theme(theme);
+        for entry in self.entries.iter_mut() {
+            entry.actor.switch_theme(theme);
+        }
     }
 
     pub(crate) fn reset(&mut self) {
-        self.background.reset();
-        self.sub.reset();
-        self.bubbles.reset();
-        self.pipes.reset();
-        self.score.reset();
+        for entry in self.entries.iter_mut() {
+            entry.actor.reset();
+        }
+        self.near_miss = false;
+    }
+
+    pub(crate) fn reseed(&mut self, seed: u64) {
+        for entry in self.entries.iter_mut() {
+            entry.actor.reseed(seed);
+        }
     }
 
     pub(crate) fn update(&mut self, state: &GameState) {
-        self.background.update(state);
-        self.sub.update(state);
-        self.bubbles.update(state);
-        self.pipes.update(state);
-        self.score.update(state);
+        for entry in self.entries.iter_mut() {
+            entry.actor.update(state);
+        }
+    }
+
+    pub(crate) fn handle_event(&mut self, event: &Event, state: &GameState) {
+        for entry in self.entries.iter_mut() {
+            entry.actor.handle_event(event, state);
+        }
+    }
+
+    /// Renders all actors that are visible in the scene
+    pub(crate) fn render(&self, scene: Scene, state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        for entry in self.entries.iter().filter(|e| e.scenes.contains(&scene)) {
+            entry.actor.render(state, canvas)?;
+        }
+
+        Ok(())
     }
-}
\ No newline at end of file
+
+    /// Delivers all events emitted so far to the game state and every actor
+    pub(crate) fn dispatch_events(&mut self, state: &mut GameState) {
+        for event in state.events.drain() {
+            if state.events.logging() {
+                eprintln!("[{:06}] {:?}", state.fc, event);
+            }
+
+            state.on_event(&event);
+            state.power_ups.on_event(&event);
+            state.oxygen.on_event(&event);
+            state.rewind.on_event(&event);
+            for entry in self.entries.iter_mut() {
+                entry.actor.on_event(&event, state);
+            }
+        }
+    }
+
+    /// Returns true when any player crashed into an obstacle. Players that collided are notified and can decide if
+    /// they crashed. Crashes and near misses are emitted as events.
+    pub(crate) fn check_collisions(&mut self, state: &GameState) -> bool {
+        let obstacles: Vec<_> = self.entries.iter()
+            .filter(|e| e.tag == CollisionTag::Obstacle)
+            .filter_map(|e| e.actor.as_collidable())
+            .flat_map(|c| c.get_bounding_boxes())
+            .collect();
+
+        let mut collided = false;
+        let mut near = false;
+        for entry in self.entries.iter_mut().filter(|e| e.tag == CollisionTag::Player) {
+            let Some(player) = entry.actor.as_collidable() else {
+                continue;
+            };
+
+            let boxes = player.get_bounding_boxes();
+            let hit = boxes.iter()
+                .any(|bb| obstacles.iter().any(|o| bb.has_intersection(*o)));
+
+            if hit {
+                if entry.actor.on_collision(state) {
+                    state.events.emit(GameEvent::Collided);
+                    collided = true;
+                }
+                continue;
+            }
+
+            near |= boxes.iter()
+                .map(|bb| Rect::new(bb.x() - NEAR_MISS_MARGIN, bb.y() - NEAR_MISS_MARGIN, bb.width() + 2 * NEAR_MISS_MARGIN as u32, bb.height() + 2 * NEAR_MISS_MARGIN as u32))
+                .any(|bb| obstacles.iter().any(|o| bb.has_intersection(*o)));
+        }
+
+        if near && !self.near_miss {
+            state.events.emit(GameEvent::NearMiss);
+        }
+        self.near_miss = near;
+
+        collided
+    }
+
+    /// Lets collectibles check if any of the players picked them up
+    pub(crate) fn check_pickups(&mut self, state: &GameState) {
+        let players: Vec<_> = self.entries.iter()
+            .filter(|e| e.tag == CollisionTag::Player)
+            .filter_map(|e| e.actor.as_collidable())
+            .flat_map(|c| c.get_bounding_boxes())
+            .collect();
+
+        for entry in self.entries.iter_mut().filter(|e| e.tag == CollisionTag::Collectible) {
+            entry.actor.collect(&players, state);
+        }
+    }
+
+    /// Lets projectiles hit obstacles. Every hit is emitted as an event, so the projectile can explode.
+    pub(crate) fn check_hits(&mut self, state: &GameState) {
+        let projectiles: Vec<_> = self.entries.iter()
+            .filter(|e| e.tag == CollisionTag::Projectile)
+            .filter_map(|e| e.actor.as_collidable())
+            .flat_map(|c| c.get_bounding_boxes())
+            .collect();
+
+        for bb in projectiles {
+            for entry in self.entries.iter_mut().filter(|e| e.tag == CollisionTag::Obstacle) {
+                if entry.actor.hit(bb, state) {
+                    state.events.emit(GameEvent::ProjectileHit { x: bb.center().x(), y: bb.center().y() });
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Snapshots of all actors, in the order they were added
+    pub(crate) fn snapshot(&self) -> Vec<Option<Box<dyn Any>>> {
+        self.entries.iter().map(|e| e.actor.snapshot()).collect()
+    }
+
+    /// Restores all actors from snapshots taken earlier
+    pub(crate) fn restore(&mut self, snapshots: Vec<Option<Box<dyn Any>>>) {
+        for (entry, snapshot) in self.entries.iter_mut().zip(snapshots) {
+            if let Some(snapshot) = snapshot {
+                entry.actor.restore(snapshot);
+            }
+        }
+        self.near_miss = false;
+    }
+
+    /// Writes the state of all actors to a save file
+    pub(crate) fn save(&self, w: &mut SaveWriter) {
+        for entry in &self.entries {
+            w.label("actor");
+            entry.actor.save(w);
+        }
+    }
+
+    /// Reads the state of all actors from a save file, in the same order they were saved
+    pub(crate) fn load(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        for entry in self.entries.iter_mut() {
+            r.expect("actor")?;
+            entry.actor.load(r)?;
+        }
+        self.near_miss = false;
+
+        Ok(())
+    }
+
+    /// Writes what spectators need to draw all actors
+    pub(crate) fn broadcast(&self, w: &mut SaveWriter) {
+        for entry in &self.entries {
+            w.label("actor");
+            entry.actor.broadcast(w);
+        }
+    }
+
+    /// Reads the actors from a broadcast frame, in the same order they were written
+    pub(crate) fn watch(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        for entry in self.entries.iter_mut() {
+            r.expect("actor")?;
+            entry.actor.watch(r)?;
+        }
+        self.near_miss = false;
+
+        Ok(())
+    }
+
+    /// Sum of the forces all actors exert on the given area
+    pub(crate) fn force_at(&self, area: Rect) -> (f32, f32) {
+        self.entries.iter()
+            .map(|e| e.actor.force_at(area))
+            .fold((0.0, 0.0), |(fx, fy), (x, y)| (fx + x, fy + y))
+    }
+
+    /// Area covered by every player that is still in the run, with the number of the player
+    pub(crate) fn player_areas(&self) -> Vec<(usize, Rect)> {
+        self.entries.iter()
+            .filter(|e| e.tag == CollisionTag::Player)
+            .filter_map(|e| Some((e.actor.player()?, e.actor.as_collidable()?)))
+            .filter_map(|(player, c)| Some((player, c.get_bounding_boxes().into_iter().reduce(|a, b| a.union(b))?)))
+            .collect()
+    }
+
+    /// Returns the area covered by all players, if any
+    pub(crate) fn player_position(&self) -> Option<Rect> {
+        self.entries.iter()
+            .filter(|e| e.tag == CollisionTag::Player)
+            .filter_map(|e| e.actor.as_collidable())
+            .flat_map(|c| c.get_bounding_boxes())
+            .reduce(|a, b| a.union(b))
+    }
+}
@@ -1,136 +1,98 @@
-use rand::Rng;
-use sdl2::image::LoadTexture;
+use std::any::Any;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
-use sdl2::render::{Texture, TextureCreator, WindowCanvas};
-use sdl2::video::WindowContext;
-use crate::{GameState, Renderable};
+use sdl2::render::WindowCanvas;
+use crate::{Collidable, GameState, Renderable};
+use crate::actors::parallax::ParallaxLayer;
+use crate::actors::terrain::Terrain;
+use crate::save::{SaveReader, SaveWriter};
 use crate::theme::Theme;
 
-#[allow(dead_code)]
-pub struct BackgroundObject {
-    x: i32,
-    y: i32,
-    texture_idx: usize,
+#[derive(Clone)]
+pub struct Background {
+    terrain: Terrain,
+    // Layers behind the sand, furthest away first
+    back_layers: Vec<ParallaxLayer>,
+    // Layers in front of the sand
+    front_layers: Vec<ParallaxLayer>,
 }
 
-impl BackgroundObject {
-    pub fn new(x: i32, y: i32, texture_idx: usize) -> Self {
-        Self { x, y, texture_idx }
-    }
-
-    pub fn update(&mut self, x_speed: i32) {
-        self.x -= x_speed;
-    }
-
-    pub fn is_finished(&self) -> bool {
-        self.x < -100
-    }
-}
-
-pub struct Background<'a> {
-    objects: Vec<BackgroundObject>,
-    new_object_at_fc: i64,
-    textures: Vec<Texture<'a>>,
-    sand_highlights: Vec<(i32, i32)>
-}
-
-impl<'a> Background<'a> {
-    pub fn new(state: &GameState, texture_creator: &'a TextureCreator<WindowContext>) -> Self {
-        let mut rng = rand::thread_rng();
-
-        let theme = state.theme.current();
-
-        let mut texture_axolotl = texture_creator.load_texture("images/axolotl.png").unwrap();
-        texture_axolotl.set_color_mod(theme.fauna_color_1.0, theme.fauna_color_1.1, theme.fauna_color_1.2);
-        let textures = vec![texture_axolotl];
-
-        // Small darker pixels in the sand
-        let mut sand_highlights = vec![];
-        for _ in 0..100 {
-            sand_highlights.push((rng.gen_range(0..800), rng.gen_range(400..600)));
-        }
-
+impl Background {
+    pub fn new(w: u32, h: u32) -> Self {
         Self {
-            objects: Vec::new(),
-            new_object_at_fc: rng.gen_range(0..100),
-            textures,
-            sand_highlights
+            terrain: Terrain::new(w, h),
+            back_layers: vec![ParallaxLayer::rocks(w), ParallaxLayer::kelp(w)],
+            front_layers: vec![ParallaxLayer::debris(w)],
         }
     }
 }
 
-impl<'a> Renderable for Background<'a> {
+impl Renderable for Background {
     fn render(&self, state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
         let theme = state.theme.current();
-        let (ww, wh) = canvas.window().size();
 
         canvas.set_draw_color(Color::RGB(theme.water.0, theme.water.1, theme.water.2));
         canvas.clear();
 
-        // Print ground line
-        let y = wh - (wh / 3);
-        canvas.set_draw_color(Color::RGB(theme.sand.0, theme.sand.1, theme.sand.2));
-        canvas.fill_rect(Rect::new(0, y as i32, ww, wh - y))?;
-
-        // Print sand highlights
-        for (x, y) in &self.sand_highlights {
-            canvas.set_draw_color(Color::RGB(theme.sand_highlight.0, theme.sand_highlight.1, theme.sand_highlight.2));
-            canvas.fill_rect(Rect::new(*x, *y, 2, 2))?;
+        for layer in &self.back_layers {
+            layer.render(state, canvas)?;
         }
 
-        // Render all objects
-        for obj in &self.objects {
-            let texture = &self.textures[obj.texture_idx];
-            let q = texture.query();
-            let rect = Rect::new(obj.x, obj.y, q.width, q.height);
-            canvas.copy(texture, None, rect)?;
+        self.terrain.render(state, canvas)?;
+
+        for layer in &self.front_layers {
+            layer.render(state, canvas)?;
         }
 
         Ok(())
     }
 
     fn update(&mut self, state: &GameState) {
-        // Update sand highlights and reset them if they go off-screen
-        for (x, y) in &mut self.sand_highlights {
-            *x -= state.x_speed;
-            if *x < 0 {
-                *x = 800;
-                *y = rand::thread_rng().gen_range(400..600);
-            }
-        }
+        self.terrain.update(state);
 
-        // Maybe add some other background elements here, sand, rocks, shipwreck, axolotl, etc.
-        if state.fc >= self.new_object_at_fc {
-            let mut rng = rand::thread_rng();
+        for layer in self.back_layers.iter_mut().chain(self.front_layers.iter_mut()) {
+            layer.update(state);
+        }
+    }
 
-            let obj = BackgroundObject::new(
-                800,
-                rng.gen_range(450..550),
-                rng.gen_range(0..self.textures.len())
-            );
+    fn switch_theme(&mut self, _theme: &Theme) {
+        // Colors are taken from the theme while rendering
+    }
 
-            self.objects.push(obj);
+    fn as_collidable(&self) -> Option<&dyn Collidable> {
+        Some(self)
+    }
 
-            self.new_object_at_fc = state.fc + rng.gen_range(50..300);
-        }
+    fn reset(&mut self) {
+        // No need to reset
+    }
 
-        // Any objects are moved here
-        for obj in &mut self.objects {
-            obj.update(state.x_speed);
-        }
+    /// The sub can crash into the dunes, so every run with the same seed gets the same dunes
+    fn reseed(&mut self, seed: u64) {
+        self.terrain.reseed(seed);
+    }
 
-        // Remove objects that are off-screen
-        self.objects.retain(|obj| !obj.is_finished());
+    fn snapshot(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.clone()))
     }
 
-    fn switch_theme(&mut self, theme: &Theme) {
-        for texture in self.textures.iter_mut() {
-            texture.set_color_mod(theme.fauna_color_1.0, theme.fauna_color_1.1, theme.fauna_color_1.2);
+    fn restore(&mut self, snapshot: Box<dyn Any>) {
+        if let Ok(background) = snapshot.downcast() {
+            *self = *background;
         }
     }
 
-    fn reset(&mut self) {
-        // No need to reset
+    /// Only the sand dunes are saved, as the sub can crash into them. The parallax layers are just for show.
+    fn save(&self, w: &mut SaveWriter) {
+        self.terrain.save(w);
+    }
+
+    fn load(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        self.terrain.load(r)
+    }
+}
+impl Collidable for Background {
+    fn get_bounding_boxes(&self) -> Vec<Rect> {
+        self.terrain.get_bounding_boxes()
     }
-}
\ No newline at end of file
+}
@@ -1,116 +1,111 @@
-use rand::Rng;
+use std::any::Any;
 use sdl2::rect::Rect;
-use sdl2::image::LoadTexture;
-use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::render::{TextureCreator, WindowCanvas};
 use sdl2::video::WindowContext;
 use crate::{GameState, Renderable};
+use crate::animation::load_sprite;
+use crate::events::GameEvent;
+use crate::particles::{Emitter, EmitterConfig, ParticleShape};
+use crate::save::{Save, SaveReader, SaveWriter};
 use crate::theme::Theme;
 
-/// Single bubble
-struct Bubble {
-    x: f32,
-    y: f32,
-    max_y: f32,
-    velocity_y: f32,
-    texture_idx: usize,
-}
-
-impl Bubble {
-    fn new(x: f32, y: f32, max_y: f32, velocity_y: f32, texture_idx: usize) -> Self {
-        Self {
-            x,
-            y,
-            velocity_y,
-            max_y,
-            texture_idx,
-        }
-    }
-
-    fn finished(&self) -> bool {
-        self.y < self.max_y || self.x < 0.0
-    }
+/// Chance that a bubble is an air pocket the sub can collect
+const AIR_POCKET_CHANCE: f64 = 0.25;
 
-    fn update(&mut self, speed: f32) {
-        self.x -= speed;
-        self.y += self.velocity_y;
-    }
-}
+/// Mixed into the seed of the run, so the bubbles do not follow the same random sequence as the pipes
+const BUBBLE_SEED: u64 = 0xb0bb_1e5e_ed00_0001;
 
-/// Bubbles is a collection of Bubble objects that are generated, rendered and updated
+/// Bubbles rising from the bottom of the ocean, popping when their time is up. Some of them are air pockets that
+/// refill the oxygen of the sub.
 pub struct Bubbles<'a> {
-    bubbles: Vec<Bubble>,
-    max_bubbles: usize,
-    textures: Vec<Texture<'a>>,
+    emitter: Emitter<'a>,
 }
 
 impl<'a> Bubbles<'a> {
     pub fn new(max_bubbles: usize, texture_creator: &'a TextureCreator<WindowContext>, w: u32, h: u32) -> Self {
-        let b_sm_texture = texture_creator.load_texture("images/bubble-sm.png").unwrap();
-        let b_md_texture = texture_creator.load_texture("images/bubble-md.png").unwrap();
-        let b_lg_texture = texture_creator.load_texture("images/bubble-lg.png").unwrap();
-        let textures = vec![b_sm_texture, b_md_texture, b_lg_texture];
-
-        let mut bubbles = Self{
-            bubbles: Vec::new(),
-            max_bubbles,
-            textures,
+        let sprites = ["images/bubble-sm.png", "images/bubble-md.png", "images/bubble-lg.png"]
+            .iter()
+            .map(|path| load_sprite(texture_creator, path).unwrap())
+            .collect();
+
+        let config = EmitterConfig {
+            rate: 1.0,
+            max_particles: max_bubbles,
+            lifetime: 60..400,
+            velocity_y: -3.0..-0.5,
+            shape: ParticleShape::Textures,
+            collectible: AIR_POCKET_CHANCE,
+            ..Default::default()
         };
 
-        for _ in 0..max_bubbles {
-            bubbles.bubbles.push(bubbles.generate(w as i32, h as i32));
-        }
+        let mut emitter = Emitter::with_textures(config, sprites);
+
+        // Bubbles start at the bottom, also a bit to the right of the screen so they flow in
+        emitter.set_spawn_area(Rect::new(0, h as i32, w + 300, 1));
+        emitter.burst(max_bubbles);
 
-        bubbles
+        Self { emitter }
     }
+}
 
-    fn generate(&self, w: i32, h: i32) -> Bubble {
-        let mut rng = rand::thread_rng();
+impl<'a> Renderable for Bubbles<'a> {
+    fn render(&self, state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        self.emitter.render(canvas, state.theme.current().bubbles)
+    }
 
-        let x = rng.gen_range(0..(w + 300));
-        let y = h as f32;
+    fn update(&mut self, state: &GameState) {
+        self.emitter.update(state.x_speed as f32);
+    }
 
-        // Maximum height of the bubble before it pops. If less than screen, than cap to top of screen (about -20)
-        let mut max_y = rng.gen_range(-200..(y / 2.0) as i32);
-        if max_y < -20 {
-            max_y = -20;
-        }
-        let velocity_y = rng.gen_range(-3.0..-0.5);
-        let texture_idx = rng.gen_range(0..self.textures.len());
+    fn switch_theme(&mut self, theme: &Theme) {
+        self.emitter.set_color_mod(theme.bubbles);
+    }
 
-        Bubble::new(x as f32, y, max_y as f32, velocity_y, texture_idx)
+    fn reset(&mut self) {
+        // No need to do anything. Bubbles will flow
     }
-}
 
-impl<'a> Renderable for Bubbles<'a> {
-    fn render(&self, _state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
-        for bubble in self.bubbles.iter() {
-            let q = &self.textures[bubble.texture_idx].query();
-            canvas.copy(&self.textures[bubble.texture_idx], None, Rect::new(bubble.x as i32, bubble.y as i32, q.width, q.height))?;
-        }
+    /// Air pockets keep the sub going, so every run with the same seed gets the same bubbles
+    fn reseed(&mut self, seed: u64) {
+        self.emitter.reseed(seed ^ BUBBLE_SEED);
+        self.emitter.burst(self.emitter.config.max_particles);
+    }
 
-        Ok(())
+    fn snapshot(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.emitter.state()))
     }
 
-    fn update(&mut self, state: &GameState) {
-        if self.bubbles.len() < self.max_bubbles {
-            let bubble = self.generate(state.window_width as i32, state.window_height as i32);
-            self.bubbles.push(bubble);
+    fn restore(&mut self, snapshot: Box<dyn Any>) {
+        if let Ok(state) = snapshot.downcast() {
+            self.emitter.restore(*state);
         }
+    }
 
-        for bubble in self.bubbles.iter_mut() {
-            bubble.update(state.x_speed as f32);
-        }
+    fn save(&self, w: &mut SaveWriter) {
+        self.emitter.state().save(w);
+    }
 
-        self.bubbles.retain(|bubble| !bubble.finished());
+    fn load(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        self.emitter.load(r)
     }
 
-    fn switch_theme(&mut self, theme: &Theme) {
-        for texture in self.textures.iter_mut() {
-            texture.set_color_mod(theme.bubbles.0, theme.bubbles.1, theme.bubbles.2);
-        }
+    fn broadcast(&self, w: &mut SaveWriter) {
+        self.emitter.broadcast(w);
     }
 
-    fn reset(&mut self) {
-        // No need to do anything. Bubbles will flow
+    fn watch(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        self.emitter.watch(r)
+    }
+
+    fn collect(&mut self, players: &[Rect], state: &GameState) {
+        if !state.game_started || state.game_over {
+            return;
+        }
+
+        for bb in players {
+            for _ in 0..self.emitter.collect(*bb) {
+                state.events.emit(GameEvent::AirCollected);
+            }
+        }
     }
 }
@@ -0,0 +1,270 @@
+use std::any::Any;
+use rand::Rng;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use crate::{GameState, Renderable};
+use crate::biome::pick_weighted;
+use crate::ecs::{self, Components, Lifetime, Transform, Velocity, World};
+use crate::events::GameEvent;
+use crate::particles::{Emitter, EmitterConfig};
+use crate::save::{load_variant, save_variant, Save, SaveReader, SaveWriter};
+use crate::theme::Theme;
+
+/// Randomly placed zones appear this far behind the pipe they spawn with
+const BEHIND_PIPE: i32 = 125;
+
+/// Width of upwellings and downdrafts, which run over the whole height of the screen
+const COLUMN_WIDTH: u32 = 90;
+
+/// Radius of a whirlpool
+const WHIRLPOOL_RADIUS: i32 = 90;
+
+/// Size of an undertow
+const UNDERTOW_SIZE: (u32, u32) = (220, 120);
+
+/// The different kinds of currents
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Kind {
+    /// Pushes up
+    Upwelling,
+    /// Pushes down
+    Downdraft,
+    /// Swirls around its center, and slowly pulls inwards
+    Whirlpool,
+    /// Pushes back
+    Undertow,
+}
+
+const ALL_CURRENTS: [Kind; 4] = [
+    Kind::Upwelling,
+    Kind::Downdraft,
+    Kind::Whirlpool,
+    Kind::Undertow,
+];
+
+/// Names of all currents, as used in the biome files
+pub fn current_names() -> Vec<&'static str> {
+    ALL_CURRENTS.iter().map(|k| k.name()).collect()
+}
+
+impl Kind {
+    fn from_name(name: &str) -> Option<Self> {
+        ALL_CURRENTS.iter().copied().find(|k| k.name() == name)
+    }
+
+    /// Name as used in the biome files
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Upwelling => "upwelling",
+            Kind::Downdraft => "downdraft",
+            Kind::Whirlpool => "whirlpool",
+            Kind::Undertow => "undertow",
+        }
+    }
+
+    /// Strength of the force at the heart of the current
+    fn strength(&self) -> f32 {
+        match self {
+            Kind::Upwelling => 0.35,
+            Kind::Downdraft => 0.3,
+            Kind::Whirlpool => 0.35,
+            Kind::Undertow => 0.6,
+        }
+    }
+}
+
+/// Current specific component
+#[derive(Clone)]
+struct CurrentData {
+    kind: Kind,
+}
+
+/// Force a current exerts on a point, relative to the center of the current
+fn force(kind: Kind, dx: f32, dy: f32) -> (f32, f32) {
+    let strength = kind.strength();
+
+    match kind {
+        Kind::Upwelling => (0.0, -strength),
+        Kind::Downdraft => (0.0, strength),
+        Kind::Undertow => (-strength, 0.0),
+        Kind::Whirlpool => {
+            let distance = (dx * dx + dy * dy).sqrt();
+            if distance > WHIRLPOOL_RADIUS as f32 || distance == 0.0 {
+                return (0.0, 0.0);
+            }
+
+            // Clockwise swirl, and a bit of a pull towards the center
+            let (nx, ny) = (dx / distance, dy / distance);
+            (-ny * strength - nx * strength * 0.3, nx * strength - ny * strength * 0.3)
+        }
+    }
+}
+
+/// Zones of moving water that push the sub around. Their flow is shown by streaming particles.
+pub struct Currents<'a> {
+    world: World<CurrentData>,
+    streams: Emitter<'a>,
+}
+
+impl<'a> Currents<'a> {
+    pub fn new() -> Self {
+        let streams = Emitter::new(EmitterConfig {
+            max_particles: 400,
+            lifetime: 25..50,
+            size: (2.0, 1.0),
+            alpha: (150, 0),
+            ..Default::default()
+        });
+
+        Self {
+            world: World::new(),
+            streams,
+        }
+    }
+
+    /// Spawns a current with its center at the given position
+    fn spawn(&mut self, kind: Kind, x: i32, y: i32, height: u32) {
+        let (w, h) = match kind {
+            Kind::Upwelling | Kind::Downdraft => (COLUMN_WIDTH, height),
+            Kind::Whirlpool => (WHIRLPOOL_RADIUS as u32 * 2, WHIRLPOOL_RADIUS as u32 * 2),
+            Kind::Undertow => UNDERTOW_SIZE,
+        };
+        let y = match kind {
+            Kind::Upwelling | Kind::Downdraft => 0,
+            _ => y - h as i32 / 2,
+        };
+
+        self.world.spawn(Components {
+            transform: Some(Transform { x: (x - w as i32 / 2) as f32, y: y as f32, w, h }),
+            velocity: Some(Velocity { dx: 0.0, dy: 0.0, scroll: true }),
+            lifetime: Some(Lifetime::OffScreen),
+            data: Some(CurrentData { kind }),
+            ..Default::default()
+        });
+    }
+
+    /// Shows the flow of every current with a few particles
+    fn stream(&mut self) {
+        let mut rng = rand::thread_rng();
+
+        for entity in self.world.entities() {
+            let (Some(t), Some(current)) = (self.world.transforms[entity], self.world.data[entity].as_ref()) else {
+                continue;
+            };
+            let area = Rect::new(t.x as i32, t.y as i32, t.w, t.h);
+
+            let (x, y) = match current.kind {
+                Kind::Upwelling => (rng.gen_range(area.left()..area.right()), area.bottom()),
+                Kind::Downdraft => (rng.gen_range(area.left()..area.right()), area.top()),
+                Kind::Undertow => (area.right(), rng.gen_range(area.top()..area.bottom())),
+                Kind::Whirlpool => {
+                    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                    let r = rng.gen_range(20.0..WHIRLPOOL_RADIUS as f32);
+                    (area.center().x() + (angle.cos() * r) as i32, area.center().y() + (angle.sin() * r) as i32)
+                }
+            };
+
+            let (dx, dy) = ((x - area.center().x()) as f32, (y - area.center().y()) as f32);
+            let (fx, fy) = force(current.kind, dx, dy);
+            // Particles are much lighter than the sub
+            let (vx, vy) = (fx * 12.0, fy * 12.0);
+
+            self.streams.config.velocity_x = vx - 0.2..vx + 0.2;
+            self.streams.config.velocity_y = vy - 0.2..vy + 0.2;
+            self.streams.set_spawn_area(Rect::new(x, y, 2, 2));
+            self.streams.burst(if current.kind == Kind::Upwelling || current.kind == Kind::Downdraft { 2 } else { 1 });
+        }
+    }
+}
+
+impl<'a> Renderable for Currents<'a> {
+    fn render(&self, state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        self.streams.render(canvas, state.theme.current().bubbles)
+    }
+
+    fn update(&mut self, state: &GameState) {
+        // Currents placed in the biome file
+        let biome = state.theme.biome();
+        for zone in biome.current_zones.iter().filter(|z| state.mode.dangers() && z.at == state.fc) {
+            if let Some(kind) = Kind::from_name(&zone.kind) {
+                self.spawn(kind, state.window_width as i32 + WHIRLPOOL_RADIUS, zone.y, state.window_height);
+            }
+        }
+
+        ecs::movement(&mut self.world, state.x_speed as f32);
+        ecs::culling(&mut self.world);
+
+        self.stream();
+        self.streams.update(state.x_speed as f32);
+    }
+
+    fn switch_theme(&mut self, _theme: &Theme) {}
+
+    fn reset(&mut self) {
+        self.world.clear();
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.world.clone()))
+    }
+
+    fn restore(&mut self, snapshot: Box<dyn Any>) {
+        if let Ok(world) = snapshot.downcast() {
+            self.world = *world;
+        }
+    }
+
+    fn save(&self, w: &mut SaveWriter) {
+        self.world.save(w);
+    }
+
+    fn load(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        self.world = Save::load(r)?;
+        Ok(())
+    }
+
+    fn on_event(&mut self, event: &GameEvent, state: &GameState) {
+        if let GameEvent::PipeSpawned { x, gap_top, gap_bottom } = event {
+            let biome = state.theme.biome();
+            let mut rng = state.rng();
+            if !rng.gen_bool(biome.current_chance as f64) {
+                return;
+            }
+
+            let table: Vec<(Kind, u32)> = biome.currents.iter()
+                .filter_map(|(name, weight)| Kind::from_name(name).map(|k| (k, *weight)))
+                .collect();
+
+            if let Some(kind) = pick_weighted(&mut *rng, &table) {
+                // The pipe already moved since it spawned
+                self.spawn(kind, x - state.x_speed + BEHIND_PIPE, (gap_top + gap_bottom) / 2, state.window_height);
+            }
+        }
+    }
+
+    fn force_at(&self, area: Rect) -> (f32, f32) {
+        self.world.entities()
+            .filter_map(|entity| Some((self.world.transforms[entity]?, self.world.data[entity].as_ref()?)))
+            .filter_map(|(t, current)| {
+                let zone = Rect::new(t.x as i32, t.y as i32, t.w, t.h);
+                if !zone.has_intersection(area) {
+                    return None;
+                }
+
+                let dx = (area.center().x() - zone.center().x()) as f32;
+                let dy = (area.center().y() - zone.center().y()) as f32;
+                Some(force(current.kind, dx, dy))
+            })
+            .fold((0.0, 0.0), |(fx, fy), (x, y)| (fx + x, fy + y))
+    }
+}
+
+impl Save for CurrentData {
+    fn save(&self, w: &mut SaveWriter) {
+        save_variant(&ALL_CURRENTS, &self.kind, w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        Ok(Self { kind: load_variant(&ALL_CURRENTS, r)? })
+    }
+}
@@ -0,0 +1,464 @@
+use std::any::Any;
+use std::f32::consts::PI;
+use rand::Rng;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::video::WindowContext;
+use crate::{Collidable, GameState, Renderable};
+use crate::biome::pick_weighted;
+use crate::ecs::{self, Collider, Components, Lifetime, Sprite, Transform, Velocity, World};
+use crate::events::GameEvent;
+use crate::save::{Save, SaveReader, SaveWriter};
+use crate::procedural::{in_ellipse, shape_texture};
+use crate::theme::Theme;
+
+/// Enemies that are not hiding in a pipe appear this far behind the pipe they spawn with
+const BEHIND_PIPE: i32 = 150;
+
+/// Pufferfish inflate when the sub comes within this distance
+const PUFF_RANGE: f32 = 140.0;
+
+/// Eels dart out when the sub is this close to the pipe, and this far out of the pipe
+const DART_RANGE: i32 = 160;
+const DART_DISTANCE: f32 = 45.0;
+
+/// An eel belongs to a pipe when their centers are at most this far apart
+const PIPE_MATCH: f32 = 5.0;
+
+/// Bonus points for destroying an enemy
+const ENEMY_POINTS: i64 = 100;
+
+/// All the hostile sea life
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Kind {
+    Pufferfish,
+    Jellyfish,
+    Eel,
+    Mine,
+}
+
+const ALL_ENEMIES: [Kind; 4] = [
+    Kind::Pufferfish,
+    Kind::Jellyfish,
+    Kind::Eel,
+    Kind::Mine,
+];
+
+/// Names of all enemies, as used in the biome spawn tables
+pub fn enemy_names() -> Vec<&'static str> {
+    ALL_ENEMIES.iter().map(|k| k.name()).collect()
+}
+
+impl Kind {
+    fn from_name(name: &str) -> Option<Self> {
+        ALL_ENEMIES.iter().copied().find(|k| k.name() == name)
+    }
+
+    /// Name as used in the biome spawn tables
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Pufferfish => "pufferfish",
+            Kind::Jellyfish => "jellyfish",
+            Kind::Eel => "eel",
+            Kind::Mine => "mine",
+        }
+    }
+
+    fn index(&self) -> usize {
+        ALL_ENEMIES.iter().position(|k| k == self).unwrap()
+    }
+
+    fn size(&self) -> (u32, u32) {
+        match self {
+            Kind::Pufferfish => (22, 22),
+            Kind::Jellyfish => (24, 32),
+            Kind::Eel => (14, 56),
+            Kind::Mine => (28, 28),
+        }
+    }
+
+    /// Enemies keep their own colors in every theme, so they always stand out
+    fn color(&self) -> (u8, u8, u8) {
+        match self {
+            Kind::Pufferfish => (235, 190, 70),
+            Kind::Jellyfish => (225, 90, 170),
+            Kind::Eel => (110, 150, 70),
+            Kind::Mine => (90, 90, 105),
+        }
+    }
+
+    fn generate_texture<'a>(&self, texture_creator: &'a TextureCreator<WindowContext>) -> Result<Texture<'a>, String> {
+        match self {
+            Kind::Pufferfish => shape_texture(texture_creator, 32, 32, |u, v| {
+                let (dx, dy) = (u - 0.5, v - 0.5);
+                let r = (dx * dx + dy * dy).sqrt();
+
+                if in_ellipse(u, v, 0.36, 0.42, 0.05, 0.05) {
+                    Some((30, 255))
+                } else if r < 0.3 {
+                    Some(if dy > 0.1 { (255, 255) } else { (210, 255) })
+                } else if r < 0.48 && (dy.atan2(dx) * 12.0).cos() > 0.85 {
+                    Some((170, 255))
+                } else {
+                    None
+                }
+            }),
+            Kind::Jellyfish => shape_texture(texture_creator, 24, 32, |u, v| {
+                let tentacle = (1..8).any(|i| {
+                    let x = i as f32 * 0.125 + ((v * 16.0 + i as f32).sin() * 0.03);
+                    v > 0.35 && (u - x).abs() < 0.03
+                });
+
+                if v < 0.4 && in_ellipse(u, v, 0.5, 0.4, 0.48, 0.36) {
+                    Some((255, 220))
+                } else if tentacle {
+                    Some((230, 200))
+                } else {
+                    None
+                }
+            }),
+            // Head pointing down, flipped when darting upwards
+            Kind::Eel => shape_texture(texture_creator, 14, 56, |u, v| {
+                let width = 0.15 + 0.3 * v.min(0.8) / 0.8;
+
+                if in_ellipse(u, v, 0.3, 0.9, 0.1, 0.025) {
+                    Some((20, 255))
+                } else if (u - 0.5).abs() < width && v < 0.95 {
+                    Some((255 - ((u - 0.5).abs() * 200.0) as u8, 255))
+                } else {
+                    None
+                }
+            }),
+            Kind::Mine => shape_texture(texture_creator, 28, 28, |u, v| {
+                let (dx, dy) = (u - 0.5, v - 0.5);
+                let r = (dx * dx + dy * dy).sqrt();
+
+                if in_ellipse(u, v, 0.4, 0.4, 0.06, 0.06) {
+                    Some((255, 255))
+                } else if r < 0.3 {
+                    Some((200 - (r * 200.0) as u8, 255))
+                } else if r < 0.46 && (dy.atan2(dx) * 8.0).cos() > 0.9 {
+                    Some((160, 255))
+                } else {
+                    None
+                }
+            }),
+        }
+    }
+}
+
+/// How an enemy moves
+#[derive(Clone)]
+enum Behaviour {
+    /// Inflates when the sub comes close (0.0 - 1.0)
+    Inflate { inflated: f32 },
+    /// Drifts up and down on a sine path
+    Drift { base_y: f32, phase: f32 },
+    /// Hides in a pipe end and darts out once when the sub approaches. Direction is 1.0 for downwards.
+    Dart { home_y: f32, direction: f32, phase: Option<f32> },
+    /// Bobs on a chain anchored to the sea floor
+    Bob { base_y: f32, phase: f32 },
+}
+
+/// Enemy specific component
+#[derive(Clone)]
+struct EnemyData {
+    behaviour: Behaviour,
+}
+
+/// Behaviour system: moves every enemy according to its behaviour. Every sub counts on its own, so an enemy does not
+/// react to the water between two subs.
+fn behaviour(world: &mut World<EnemyData>, subs: &[Rect]) {
+    for entity in world.entities().collect::<Vec<_>>() {
+        let (Some(t), Some(v), Some(sprite), Some(collider), Some(enemy)) = (
+            &mut world.transforms[entity],
+            &mut world.velocities[entity],
+            &mut world.sprites[entity],
+            &mut world.colliders[entity],
+            &mut world.data[entity],
+        ) else {
+            continue;
+        };
+
+        match &mut enemy.behaviour {
+            Behaviour::Inflate { inflated } => {
+                let (cx, cy) = (t.x + t.w as f32 / 2.0, t.y + t.h as f32 / 2.0);
+
+                let near = subs.iter().any(|p| {
+                    let (dx, dy) = (p.center().x() as f32 - cx, p.center().y() as f32 - cy);
+                    (dx * dx + dy * dy).sqrt() < PUFF_RANGE
+                });
+                *inflated = (*inflated + if near { 0.05 } else { -0.02 }).clamp(0.0, 1.0);
+
+                // Grow around the center
+                let size = (Kind::Pufferfish.size().0 as f32 + *inflated * 18.0) as u32;
+                t.x = cx - size as f32 / 2.0;
+                t.y = cy - size as f32 / 2.0;
+                t.w = size;
+                t.h = size;
+                sprite.parts[0].dst = Rect::new(0, 0, size, size);
+                collider.boxes = vec![Rect::new(size as i32 / 6, size as i32 / 6, size * 2 / 3, size * 2 / 3)];
+                v.dx = -0.5;
+            }
+            Behaviour::Drift { base_y, phase } => {
+                *phase += 0.03;
+                v.dx = -0.3;
+                v.dy = *base_y + phase.sin() * 60.0 - t.y;
+            }
+            Behaviour::Dart { home_y, direction, phase } => {
+                if phase.is_none() && subs.iter().any(|p| p.right() > t.x as i32 - DART_RANGE && p.left() < t.x as i32 + t.w as i32) {
+                    *phase = Some(0.0);
+                }
+
+                // Out and back in again, once
+                let out = match phase {
+                    Some(p) => {
+                        *p = (*p + PI / 60.0).min(PI);
+                        p.sin()
+                    }
+                    None => 0.0,
+                };
+                v.dy = *home_y + *direction * out * DART_DISTANCE - t.y;
+            }
+            Behaviour::Bob { base_y, phase } => {
+                *phase += 0.05;
+                v.dy = *base_y + phase.sin() * 8.0 - t.y;
+            }
+        }
+    }
+}
+
+/// Hostile sea life. Enemies appear together with new pipes, depending on the spawn table of the current biome.
+pub struct Enemies<'a> {
+    textures: Vec<Texture<'a>>,
+    world: World<EnemyData>,
+}
+
+impl<'a> Enemies<'a> {
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>) -> Self {
+        let textures = ALL_ENEMIES.iter()
+            .map(|k| {
+                let mut texture = k.generate_texture(texture_creator).unwrap();
+                texture.set_color_mod(k.color().0, k.color().1, k.color().2);
+                texture
+            })
+            .collect();
+
+        Self {
+            textures,
+            world: World::new(),
+        }
+    }
+
+    /// Picks an enemy from the weighted spawn table of the current biome
+    fn pick_enemy(rng: &mut impl Rng, state: &GameState) -> Option<Kind> {
+        let table: Vec<(Kind, u32)> = state.theme.biome().enemies.iter()
+            .filter_map(|(name, weight)| Kind::from_name(name).map(|k| (k, *weight)))
+            .collect();
+
+        pick_weighted(rng, &table)
+    }
+
+    /// Keeps the eels hiding in the pipe at x in the ends of the pipe, when its gap moved
+    fn follow_pipe(&mut self, x: i32, gap_top: i32, gap_bottom: i32) {
+        for entity in self.world.entities().collect::<Vec<_>>() {
+            let (Some(t), Some(EnemyData { behaviour: Behaviour::Dart { home_y, direction, .. } })) = (self.world.transforms[entity], self.world.data[entity].as_mut()) else {
+                continue;
+            };
+            if (t.x + t.w as f32 / 2.0 - (x + 25) as f32).abs() > PIPE_MATCH {
+                continue;
+            }
+
+            *home_y = if *direction > 0.0 { (gap_top - t.h as i32) as f32 } else { gap_bottom as f32 };
+        }
+    }
+
+    /// Spawns an enemy that belongs to the pipe at x with the given gap
+    fn spawn(&mut self, rng: &mut impl Rng, kind: Kind, x: i32, gap_top: i32, gap_bottom: i32) {
+        let (w, h) = kind.size();
+
+        let (x, y, behaviour) = match kind {
+            Kind::Pufferfish => {
+                (x + BEHIND_PIPE, rng.gen_range(120..420) as f32, Behaviour::Inflate { inflated: 0.0 })
+            }
+            Kind::Jellyfish => {
+                let y = rng.gen_range(150..380) as f32;
+                (x + BEHIND_PIPE, y, Behaviour::Drift { base_y: y, phase: rng.gen_range(0.0..2.0 * PI) })
+            }
+            Kind::Eel => {
+                // Hidden in the top or bottom end of the pipe
+                let x = x + 25 - w as i32 / 2;
+                if rng.gen_bool(0.5) {
+                    let y = (gap_top - h as i32) as f32;
+                    (x, y, Behaviour::Dart { home_y: y, direction: 1.0, phase: None })
+                } else {
+                    let y = gap_bottom as f32;
+                    (x, y, Behaviour::Dart { home_y: y, direction: -1.0, phase: None })
+                }
+            }
+            Kind::Mine => {
+                let y = rng.gen_range(300..460) as f32;
+                (x + BEHIND_PIPE, y, Behaviour::Bob { base_y: y, phase: rng.gen_range(0.0..2.0 * PI) })
+            }
+        };
+
+        let mut sprite = Sprite::single(kind.index(), None, w, h);
+        if let Behaviour::Dart { direction, .. } = behaviour {
+            sprite.parts[0].flip_vertical = direction < 0.0;
+        }
+
+        let collider = match kind {
+            Kind::Jellyfish => Rect::new(2, 0, w - 4, h / 2),
+            Kind::Mine => Rect::new(4, 4, w - 8, h - 8),
+            _ => Rect::new(0, 0, w, h),
+        };
+
+        self.world.spawn(Components {
+            transform: Some(Transform { x: x as f32, y, w, h }),
+            velocity: Some(Velocity { dx: 0.0, dy: 0.0, scroll: true }),
+            sprite: Some(sprite),
+            collider: Some(Collider { boxes: vec![collider] }),
+            lifetime: Some(Lifetime::OffScreen),
+            data: Some(EnemyData { behaviour }),
+            ..Default::default()
+        });
+    }
+}
+
+impl<'a> Renderable for Enemies<'a> {
+    fn render(&self, state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        // Chains of the mines, down to the sea floor
+        canvas.set_draw_color(Color::RGB(60, 60, 70));
+        for entity in self.world.entities() {
+            let (Some(t), Some(EnemyData { behaviour: Behaviour::Bob { .. } })) = (self.world.transforms[entity], &self.world.data[entity]) else {
+                continue;
+            };
+
+            let cx = t.x as i32 + t.w as i32 / 2;
+            for y in ((t.y as i32 + t.h as i32)..state.window_height as i32).step_by(6) {
+                canvas.fill_rect(Rect::new(cx - 1, y, 2, 4))?;
+            }
+        }
+
+        ecs::render(&self.world, &self.textures, canvas)
+    }
+
+    fn update(&mut self, state: &GameState) {
+        let subs: Vec<Rect> = state.player_areas.iter().map(|(_, area)| *area).collect();
+        behaviour(&mut self.world, &subs);
+        ecs::movement(&mut self.world, state.x_speed as f32);
+        ecs::culling(&mut self.world);
+    }
+
+    fn switch_theme(&mut self, _theme: &Theme) {
+        // Enemies keep their own colors
+    }
+
+    fn reset(&mut self) {
+        self.world.clear();
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.world.clone()))
+    }
+
+    fn restore(&mut self, snapshot: Box<dyn Any>) {
+        if let Ok(world) = snapshot.downcast() {
+            self.world = *world;
+        }
+    }
+
+    fn save(&self, w: &mut SaveWriter) {
+        self.world.save(w);
+    }
+
+    fn load(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        self.world = Save::load(r)?;
+        ecs::check_textures(&self.world, self.textures.len())?;
+        Ok(())
+    }
+
+    fn as_collidable(&self) -> Option<&dyn Collidable> {
+        Some(self)
+    }
+
+    fn hit(&mut self, area: Rect, state: &GameState) -> bool {
+        let hit = ecs::colliding(&self.world, area);
+        for entity in &hit {
+            self.world.despawn(*entity);
+            state.events.emit(GameEvent::Destroyed { points: ENEMY_POINTS });
+        }
+
+        !hit.is_empty()
+    }
+
+    fn on_event(&mut self, event: &GameEvent, state: &GameState) {
+        match event {
+            GameEvent::PipeSpawned { x, gap_top, gap_bottom } => {
+                let mut rng = state.rng();
+                if !rng.gen_bool(state.theme.biome().enemy_chance as f64) {
+                    return;
+                }
+
+                if let Some(kind) = Self::pick_enemy(&mut *rng, state) {
+                    // The pipe already moved since it spawned
+                    self.spawn(&mut *rng, kind, x - state.x_speed, *gap_top, *gap_bottom);
+                }
+            }
+            GameEvent::PipeMoved { x, gap_top, gap_bottom } => self.follow_pipe(*x, *gap_top, *gap_bottom),
+            _ => {}
+        }
+    }
+}
+
+impl<'a> Collidable for Enemies<'a> {
+    fn get_bounding_boxes(&self) -> Vec<Rect> {
+        ecs::bounding_boxes(&self.world)
+    }
+}
+
+impl Save for EnemyData {
+    fn save(&self, w: &mut SaveWriter) {
+        match self.behaviour {
+            Behaviour::Inflate { inflated } => {
+                w.label("inflate");
+                inflated.save(w);
+            }
+            Behaviour::Drift { base_y, phase } => {
+                w.label("drift");
+                (base_y, phase).save(w);
+            }
+            Behaviour::Dart { home_y, direction, phase } => {
+                w.label("dart");
+                (home_y, direction).save(w);
+                phase.save(w);
+            }
+            Behaviour::Bob { base_y, phase } => {
+                w.label("bob");
+                (base_y, phase).save(w);
+            }
+        }
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let behaviour = match r.label()? {
+            "inflate" => Behaviour::Inflate { inflated: Save::load(r)? },
+            "drift" => {
+                let (base_y, phase) = Save::load(r)?;
+                Behaviour::Drift { base_y, phase }
+            }
+            "dart" => {
+                let (home_y, direction) = Save::load(r)?;
+                Behaviour::Dart { home_y, direction, phase: Save::load(r)? }
+            }
+            "bob" => {
+                let (base_y, phase) = Save::load(r)?;
+                Behaviour::Bob { base_y, phase }
+            }
+            label => return Err(format!("unknown enemy behaviour '{}' in save file", label)),
+        };
+
+        Ok(Self { behaviour })
+    }
+}
@@ -0,0 +1,331 @@
+use std::f32::consts::PI;
+use rand::Rng;
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::video::WindowContext;
+use crate::{GameState, Renderable};
+use crate::animation::{load_sprite, Animation, AnimationPlayer};
+use crate::biome::pick_weighted;
+use crate::ecs::{self, Components, Lifetime, Sprite, Transform, Velocity, World};
+use crate::procedural::{distance_to_segment, in_ellipse, shape_texture};
+use crate::theme::Theme;
+
+/// How a creature moves through the water
+#[derive(Clone, Copy, PartialEq)]
+enum Behaviour {
+    /// Lies still on the sand
+    Still,
+    /// Swims through the water in a wavy line
+    Swim,
+    /// Walks back and forth along the sand
+    Scuttle,
+    /// Drifts upwards in pulses
+    Pulse,
+}
+
+/// All the different creatures that live in the ocean
+#[derive(Clone, Copy, PartialEq)]
+enum Species {
+    Axolotl,
+    Fish,
+    Crab,
+    Starfish,
+    Jellyfish,
+    Coral,
+}
+
+const ALL_SPECIES: [Species; 6] = [
+    Species::Axolotl,
+    Species::Fish,
+    Species::Crab,
+    Species::Starfish,
+    Species::Jellyfish,
+    Species::Coral,
+];
+
+/// Names of all species, as used in the biome spawn tables
+pub fn species_names() -> Vec<&'static str> {
+    ALL_SPECIES.iter().map(|s| s.name()).collect()
+}
+
+impl Species {
+    fn from_name(name: &str) -> Option<Self> {
+        ALL_SPECIES.iter().copied().find(|s| s.name() == name)
+    }
+
+    /// Name as used in the biome spawn tables
+    fn name(&self) -> &'static str {
+        match self {
+            Species::Axolotl => "axolotl",
+            Species::Fish => "fish",
+            Species::Crab => "crab",
+            Species::Starfish => "starfish",
+            Species::Jellyfish => "jellyfish",
+            Species::Coral => "coral",
+        }
+    }
+
+    /// Theme fauna color slot (1-4) this species is tinted with
+    fn color_slot(&self) -> usize {
+        match self {
+            Species::Axolotl => 1,
+            Species::Fish => 1,
+            Species::Crab => 3,
+            Species::Starfish => 4,
+            Species::Jellyfish => 4,
+            Species::Coral => 2,
+        }
+    }
+
+    fn behaviour(&self) -> Behaviour {
+        match self {
+            Species::Axolotl => Behaviour::Scuttle,
+            Species::Fish => Behaviour::Swim,
+            Species::Crab => Behaviour::Scuttle,
+            Species::Starfish => Behaviour::Still,
+            Species::Jellyfish => Behaviour::Pulse,
+            Species::Coral => Behaviour::Still,
+        }
+    }
+
+    /// Vertical range in which the species spawns
+    fn spawn_y(&self) -> std::ops::Range<i32> {
+        match self.behaviour() {
+            Behaviour::Swim => 80..360,
+            Behaviour::Pulse => 250..450,
+            _ => 450..550,
+        }
+    }
+
+    /// Loads the sprite of the species. Species without artwork are generated and have a single frame.
+    fn load_sprite<'a>(&self, texture_creator: &'a TextureCreator<WindowContext>) -> Result<(Texture<'a>, Animation), String> {
+        let texture = match self {
+            Species::Axolotl => return load_sprite(texture_creator, "images/axolotl.png"),
+            Species::Fish => shape_texture(texture_creator, 32, 20, |u, v| {
+                if in_ellipse(u, v, 0.2, 0.4, 0.06, 0.1) {
+                    Some((40, 255))
+                } else if in_ellipse(u, v, 0.4, 0.5, 0.36, 0.4) {
+                    Some((255 - (v * 80.0) as u8, 255))
+                } else if u > 0.7 && (v - 0.5).abs() < (u - 0.7) * 1.6 {
+                    Some((200, 255))
+                } else {
+                    None
+                }
+            }),
+            Species::Crab => shape_texture(texture_creator, 32, 20, |u, v| {
+                let leg = (0..3).any(|i| {
+                    let x = 0.3 + i as f32 * 0.2;
+                    distance_to_segment(u, v, x, 0.6, x - 0.12, 0.95) < 0.04
+                        || distance_to_segment(u, v, x, 0.6, x + 0.12, 0.95) < 0.04
+                });
+
+                if in_ellipse(u, v, 0.42, 0.22, 0.04, 0.08) || in_ellipse(u, v, 0.58, 0.22, 0.04, 0.08) {
+                    Some((40, 255))
+                } else if in_ellipse(u, v, 0.5, 0.55, 0.3, 0.3) || in_ellipse(u, v, 0.1, 0.3, 0.1, 0.18) || in_ellipse(u, v, 0.9, 0.3, 0.1, 0.18) {
+                    Some((255, 255))
+                } else if leg {
+                    Some((190, 255))
+                } else {
+                    None
+                }
+            }),
+            Species::Starfish => shape_texture(texture_creator, 24, 24, |u, v| {
+                let (dx, dy) = (u - 0.5, v - 0.5);
+                let r = (dx * dx + dy * dy).sqrt();
+                let arm = ((dy.atan2(dx) * 5.0 + PI / 2.0).cos() + 1.0) / 2.0;
+
+                if r < 0.15 + 0.33 * arm * arm {
+                    Some((255 - (r * 200.0) as u8, 255))
+                } else {
+                    None
+                }
+            }),
+            Species::Jellyfish => shape_texture(texture_creator, 24, 32, |u, v| {
+                let tentacle = (1..5).any(|i| {
+                    let x = i as f32 * 0.2 + ((v * 12.0).sin() * 0.04);
+                    v > 0.4 && (u - x).abs() < 0.035
+                });
+
+                if v < 0.45 && in_ellipse(u, v, 0.5, 0.45, 0.45, 0.4) {
+                    Some((255, 190))
+                } else if tentacle {
+                    Some((220, 150))
+                } else {
+                    None
+                }
+            }),
+            Species::Coral => shape_texture(texture_creator, 24, 36, |u, v| {
+                let branches = [
+                    (0.5, 1.0, 0.5, 0.3),
+                    (0.5, 0.7, 0.2, 0.35),
+                    (0.5, 0.55, 0.8, 0.15),
+                    (0.2, 0.35, 0.1, 0.1),
+                    (0.35, 0.55, 0.25, 0.05),
+                ];
+
+                let d = branches.iter()
+                    .map(|(x1, y1, x2, y2)| distance_to_segment(u, v, *x1, *y1, *x2, *y2))
+                    .fold(f32::MAX, f32::min);
+
+                if d < 0.07 {
+                    Some((255 - (d * 1000.0) as u8, 255))
+                } else {
+                    None
+                }
+            }),
+        }?;
+
+        let q = texture.query();
+        Ok((texture, Animation::single(q.width, q.height)))
+    }
+}
+
+fn species_index(species: Species) -> usize {
+    ALL_SPECIES.iter().position(|s| *s == species).unwrap()
+}
+
+/// Creature specific component
+#[derive(Clone)]
+struct CreatureData {
+    species: Species,
+    base_y: f32,
+    // Speed at which the creature moves by itself (on top of the scrolling)
+    speed: f32,
+    // Phase of the current movement cycle
+    phase: f32,
+    scale: f32,
+    player: AnimationPlayer,
+}
+
+/// Behaviour system: steers every creature according to the behaviour of its species, and keeps its sprite in sync
+/// with its animation.
+fn behaviour(world: &mut World<CreatureData>, animations: &[Animation]) {
+    for entity in world.entities().collect::<Vec<_>>() {
+        let (Some(t), Some(v), Some(sprite), Some(c)) = (
+            &world.transforms[entity],
+            &mut world.velocities[entity],
+            &mut world.sprites[entity],
+            &mut world.data[entity],
+        ) else {
+            continue;
+        };
+
+        let animation = &animations[species_index(c.species)];
+        c.player.update(animation);
+
+        match c.species.behaviour() {
+            Behaviour::Still => {}
+            Behaviour::Swim => {
+                c.phase += 0.05;
+                v.dx = -c.speed;
+                v.dy = c.base_y + c.phase.sin() * 15.0 - t.y;
+            }
+            Behaviour::Scuttle => {
+                c.phase += 0.03;
+                v.dx = -c.phase.sin() * c.speed;
+            }
+            Behaviour::Pulse => {
+                c.phase = (c.phase + 0.04) % (2.0 * PI);
+                // Contract quickly and push upwards, then relax and sink slowly
+                let push = c.phase.sin().max(0.0);
+                c.scale = 1.0 - push * 0.15;
+                v.dy = -(push * c.speed - 0.2);
+            }
+        }
+
+        let (fw, fh) = animation.frame_size();
+        let part = &mut sprite.parts[0];
+        part.src = Some(c.player.frame_rect(animation));
+        part.dst.set_width((fw as f32 * c.scale) as u32);
+        part.dst.set_height((fh as f32 * (2.0 - c.scale)) as u32);
+        part.flip_horizontal = c.species.behaviour() == Behaviour::Swim && c.speed < 0.0;
+    }
+}
+
+/// All the creatures living in the background. What spawns depends on the spawn table of the current biome.
+pub struct Fauna<'a> {
+    world: World<CreatureData>,
+    textures: Vec<Texture<'a>>,
+    animations: Vec<Animation>,
+    new_creature_at_fc: i64,
+}
+
+impl<'a> Fauna<'a> {
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>) -> Self {
+        let (textures, animations) = ALL_SPECIES.iter()
+            .map(|s| s.load_sprite(texture_creator).unwrap())
+            .unzip();
+
+        Self {
+            world: World::new(),
+            textures,
+            animations,
+            new_creature_at_fc: rand::thread_rng().gen_range(0..100),
+        }
+    }
+
+    /// Picks a species from the weighted spawn table of the current biome
+    fn pick_species(state: &GameState) -> Option<Species> {
+        let table: Vec<(Species, u32)> = state.theme.biome().fauna.iter()
+            .filter_map(|(name, weight)| Species::from_name(name).map(|s| (s, *weight)))
+            .collect();
+
+        pick_weighted(&mut rand::thread_rng(), &table)
+    }
+}
+
+impl<'a> Renderable for Fauna<'a> {
+    fn render(&self, _state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        ecs::render(&self.world, &self.textures, canvas)
+    }
+
+    fn update(&mut self, state: &GameState) {
+        if state.fc >= self.new_creature_at_fc {
+            let mut rng = rand::thread_rng();
+
+            if let Some(species) = Self::pick_species(state) {
+                let speed = match species.behaviour() {
+                    Behaviour::Swim => rng.gen_range(-1.5..2.0),
+                    Behaviour::Scuttle => rng.gen_range(0.5..1.5),
+                    Behaviour::Pulse => rng.gen_range(1.0..2.0),
+                    Behaviour::Still => 0.0,
+                };
+
+                let y = rng.gen_range(species.spawn_y()) as f32;
+                let (w, h) = self.animations[species_index(species)].frame_size();
+
+                self.world.spawn(Components {
+                    transform: Some(Transform { x: state.window_width as f32, y, w, h }),
+                    velocity: Some(Velocity { dx: 0.0, dy: 0.0, scroll: true }),
+                    sprite: Some(Sprite::single(species_index(species), None, w, h)),
+                    lifetime: Some(Lifetime::OffScreen),
+                    data: Some(CreatureData {
+                        species,
+                        base_y: y,
+                        speed,
+                        phase: rng.gen_range(0.0..2.0 * PI),
+                        scale: 1.0,
+                        player: AnimationPlayer::new(),
+                    }),
+                    ..Default::default()
+                });
+            }
+
+            self.new_creature_at_fc = state.fc + rng.gen_range(30..200);
+        }
+
+        behaviour(&mut self.world, &self.animations);
+        ecs::movement(&mut self.world, state.x_speed as f32);
+        ecs::culling(&mut self.world);
+    }
+
+    fn switch_theme(&mut self, theme: &Theme) {
+        for (species, texture) in ALL_SPECIES.iter().zip(self.textures.iter_mut()) {
+            let color = theme.fauna_color(species.color_slot());
+            texture.set_color_mod(color.0, color.1, color.2);
+        }
+    }
+
+    fn reset(&mut self) {
+        // Creatures keep on living between runs
+    }
+}
@@ -0,0 +1,63 @@
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Texture, TextureCreator, WindowCanvas};
+use sdl2::video::WindowContext;
+use crate::{GameState, Renderable, PLAYER_X};
+use crate::actors::sub::SUB_SIZE;
+use crate::animation::{load_sprite, Animation, AnimationPlayer};
+use crate::theme::Theme;
+
+/// Ghosts are see-through
+const GHOST_ALPHA: u8 = 100;
+
+/// Subs of the other racers in a network race. Their position on the screen shows how far they are ahead or behind.
+pub struct Ghosts<'a> {
+    texture: Texture<'a>,
+    animation: Animation,
+    player: AnimationPlayer,
+}
+
+impl<'a> Ghosts<'a> {
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>) -> Self {
+        let (mut texture, animation) = load_sprite(texture_creator, "images/sub-large.png").unwrap();
+        texture.set_blend_mode(BlendMode::Blend);
+        texture.set_alpha_mod(GHOST_ALPHA);
+
+        Self {
+            texture,
+            animation,
+            player: AnimationPlayer::new(),
+        }
+    }
+}
+
+impl<'a> Renderable for Ghosts<'a> {
+    fn render(&self, state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let Some(net) = &state.net else {
+            return Ok(());
+        };
+
+        let (w, h) = SUB_SIZE;
+        for ghost in net.ghosts() {
+            let x = PLAYER_X + (ghost.distance - state.distance) as i32;
+            if x + (w as i32) < 0 || x > state.window_width as i32 {
+                continue;
+            }
+
+            let angle = if ghost.sunk { 180.0 } else { 0.0 };
+            canvas.copy_ex(&self.texture, self.player.frame_rect(&self.animation), Rect::new(x, ghost.y, w, h), angle, None, false, false)?;
+        }
+
+        Ok(())
+    }
+
+    fn update(&mut self, _state: &GameState) {
+        self.player.update(&self.animation);
+    }
+
+    fn switch_theme(&mut self, theme: &Theme) {
+        self.texture.set_color_mod(theme.sub.0, theme.sub.1, theme.sub.2);
+    }
+
+    fn reset(&mut self) {
+    }
+}
@@ -0,0 +1,188 @@
+use rand::Rng;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use crate::GameState;
+
+/// Width of a single column in the rock silhouette
+const ROCK_COLUMN_WIDTH: i32 = 8;
+
+/// A single kelp stalk or piece of debris
+#[derive(Clone)]
+struct Item {
+    x: f32,
+    size: i32,
+    phase: f32,
+    visible: bool,
+}
+
+/// What is drawn on a layer
+#[derive(Clone)]
+enum LayerKind {
+    /// Distant rock silhouettes
+    Rocks { heights: Vec<i32> },
+    /// Swaying kelp stalks
+    Kelp { items: Vec<Item> },
+    /// Pebbles and shells in front of the sand
+    Debris { items: Vec<Item> },
+}
+
+/// A background layer that scrolls at a fraction of the game speed. Everything is generated, so no artwork is needed.
+#[derive(Clone)]
+pub struct ParallaxLayer {
+    kind: LayerKind,
+    offset: f32,
+}
+
+impl ParallaxLayer {
+    pub fn rocks(w: u32) -> Self {
+        let mut rng = rand::thread_rng();
+
+        // Random walk, which is smoothed afterward. Twice the screen width so it can wrap around.
+        let columns = (2 * w as i32 / ROCK_COLUMN_WIDTH) as usize;
+        let mut heights = Vec::with_capacity(columns);
+        let mut h = 100.0f32;
+        for _ in 0..columns {
+            h = (h + rng.gen_range(-12.0..12.0)).clamp(30.0, 220.0);
+            heights.push(h);
+        }
+
+        // Make sure both ends match up, so the wrap-around is not noticeable
+        let smoothed = (0..columns)
+            .map(|i| {
+                let sum: f32 = (0..7).map(|d| heights[(i + columns + d - 3) % columns]).sum();
+                (sum / 7.0) as i32
+            })
+            .collect();
+
+        Self { kind: LayerKind::Rocks { heights: smoothed }, offset: 0.0 }
+    }
+
+    pub fn kelp(w: u32) -> Self {
+        let mut rng = rand::thread_rng();
+
+        let items = (0..w / 40)
+            .map(|i| Item {
+                x: (i * 40) as f32 + rng.gen_range(0.0..30.0),
+                size: rng.gen_range(12..26),
+                phase: rng.gen_range(0.0..6.0),
+                visible: rng.gen_bool(0.5),
+            })
+            .collect();
+
+        Self { kind: LayerKind::Kelp { items }, offset: 0.0 }
+    }
+
+    pub fn debris(w: u32) -> Self {
+        let mut rng = rand::thread_rng();
+
+        let items = (0..w / 25)
+            .map(|i| Item {
+                x: (i * 25) as f32 + rng.gen_range(0.0..20.0),
+                size: rng.gen_range(2..6),
+                phase: rng.gen_range(0.0..1.0),
+                visible: rng.gen_bool(0.5),
+            })
+            .collect();
+
+        Self { kind: LayerKind::Debris { items }, offset: 0.0 }
+    }
+
+    /// Fraction of the game speed this layer scrolls at, as configured by the current biome
+    fn speed_factor(&self, state: &GameState) -> f32 {
+        let biome = state.theme.biome();
+        match self.kind {
+            LayerKind::Rocks { .. } => biome.rocks_speed,
+            LayerKind::Kelp { .. } => biome.kelp_speed,
+            LayerKind::Debris { .. } => biome.debris_speed,
+        }
+    }
+
+    pub fn update(&mut self, state: &GameState) {
+        let dx = state.x_speed as f32 * self.speed_factor(state);
+        let w = state.window_width as f32;
+        let biome = state.theme.biome();
+
+        match &mut self.kind {
+            LayerKind::Rocks { heights } => {
+                let total = (heights.len() as i32 * ROCK_COLUMN_WIDTH) as f32;
+                self.offset = (self.offset + dx) % total;
+            }
+            LayerKind::Kelp { items } => {
+                let mut rng = rand::thread_rng();
+                for item in items.iter_mut() {
+                    item.x -= dx;
+                    item.phase += 0.03;
+                    if item.x < -40.0 {
+                        item.x += w + 80.0;
+                        item.size = rng.gen_range(12..26);
+                        item.visible = rng.gen_bool(biome.kelp_density as f64);
+                    }
+                }
+            }
+            LayerKind::Debris { items } => {
+                let mut rng = rand::thread_rng();
+                for item in items.iter_mut() {
+                    item.x -= dx;
+                    if item.x < -10.0 {
+                        item.x += w + 20.0;
+                        item.size = rng.gen_range(2..6);
+                        item.phase = rng.gen_range(0.0..1.0);
+                        item.visible = rng.gen_bool(biome.debris_density as f64);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn render(&self, state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let theme = state.theme.current();
+        let (ww, wh) = (state.window_width as i32, state.window_height as i32);
+        let ground = wh - (wh / 3);
+
+        match &self.kind {
+            LayerKind::Rocks { heights } => {
+                canvas.set_draw_color(Color::RGB(theme.rocks.0, theme.rocks.1, theme.rocks.2));
+
+                let first = (self.offset as i32) / ROCK_COLUMN_WIDTH;
+                let shift = (self.offset as i32) % ROCK_COLUMN_WIDTH;
+                for i in 0..=(ww / ROCK_COLUMN_WIDTH + 1) {
+                    let h = heights[(first + i) as usize % heights.len()];
+                    let x = i * ROCK_COLUMN_WIDTH - shift;
+                    canvas.fill_rect(Rect::new(x, ground - h, ROCK_COLUMN_WIDTH as u32, h as u32))?;
+                }
+            }
+            LayerKind::Kelp { items } => {
+                canvas.set_draw_color(Color::RGB(theme.kelp.0, theme.kelp.1, theme.kelp.2));
+
+                for item in items.iter().filter(|i| i.visible) {
+                    for segment in 0..item.size {
+                        // The higher up the stalk, the more it sways
+                        let sway = (item.phase + segment as f32 * 0.35).sin() * 6.0 * segment as f32 / item.size as f32;
+                        let x = item.x as i32 + sway as i32;
+                        // Planted deep enough to stay hidden behind the dunes
+                        let y = ground + 40 - segment * 8;
+                        canvas.fill_rect(Rect::new(x, y, 4, 9))?;
+
+                        // Leaves on every third segment, alternating sides
+                        if segment % 3 == 2 {
+                            let side = if segment % 6 == 2 { -7 } else { 4 };
+                            canvas.fill_rect(Rect::new(x + side, y + 2, 7, 3))?;
+                        }
+                    }
+                }
+            }
+            LayerKind::Debris { items } => {
+                canvas.set_draw_color(Color::RGB(theme.sand_highlight.0, theme.sand_highlight.1, theme.sand_highlight.2));
+
+                // Deep enough in the sand to stay below the surface of the dunes
+                for item in items.iter().filter(|i| i.visible) {
+                    let y = ground + 40 + (item.phase * (wh - ground - 48) as f32) as i32;
+                    canvas.fill_rect(Rect::new(item.x as i32, y, item.size as u32 + 2, item.size as u32))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use std::any::Any;
+use rand::Rng;
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::video::WindowContext;
+use crate::{GameState, Renderable};
+use crate::ecs::{self, Collider, Components, Lifetime, Sprite, Transform, Velocity, World};
+use crate::events::GameEvent;
+use crate::powerup::{PowerUp, ALL_POWERUPS};
+use crate::save::{Save, SaveReader, SaveWriter};
+use crate::procedural::{distance_to_segment, in_ellipse, shape_texture};
+use crate::theme::Theme;
+
+/// Size of a pickup on screen
+const PICKUP_SIZE: u32 = 24;
+
+/// Chance that a power-up spawns in the gap of a new pipe
+const SPAWN_CHANCE: f64 = 0.35;
+
+/// Collectibles are pulled towards the sub from this distance when the magnet is active
+const MAGNET_RANGE: f32 = 250.0;
+const MAGNET_SPEED: f32 = 6.0;
+
+/// Chance that a pickup is a crate of torpedoes instead of a power-up
+const AMMO_CHANCE: f64 = 0.25;
+
+/// Torpedoes in a crate
+const AMMO_PER_CRATE: u32 = 2;
+
+/// What can be picked up
+#[derive(Clone, Copy)]
+enum Item {
+    PowerUp(PowerUp),
+    Ammo,
+}
+
+impl Item {
+    /// Textures are ordered as the power-ups, followed by the ammo crate
+    fn texture(&self) -> usize {
+        match self {
+            Item::PowerUp(p) => p.index(),
+            Item::Ammo => ALL_POWERUPS.len(),
+        }
+    }
+}
+
+/// Pickup specific component
+#[derive(Clone)]
+struct PickupData {
+    item: Item,
+    base_y: f32,
+    phase: f32,
+}
+
+/// Power-ups and torpedo crates floating in the gaps of the pipes, waiting to be picked up
+pub struct Pickups<'a> {
+    textures: Vec<Texture<'a>>,
+    world: World<PickupData>,
+}
+
+impl<'a> Pickups<'a> {
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>) -> Self {
+        let mut textures: Vec<_> = ALL_POWERUPS.iter()
+            .map(|p| {
+                let mut texture = Self::generate_texture(texture_creator, *p).unwrap();
+                texture.set_color_mod(p.color().0, p.color().1, p.color().2);
+                texture
+            })
+            .collect();
+        textures.push(Self::generate_ammo_texture(texture_creator).unwrap());
+
+        Self {
+            textures,
+            world: World::new(),
+        }
+    }
+
+    /// An orb with a symbol of the power-up inside
+    fn generate_texture(texture_creator: &'a TextureCreator<WindowContext>, power_up: PowerUp) -> Result<Texture<'a>, String> {
+        shape_texture(texture_creator, PICKUP_SIZE, PICKUP_SIZE, move |u, v| {
+            let (dx, dy) = (u - 0.5, v - 0.5);
+            let r = (dx * dx + dy * dy).sqrt();
+
+            if r > 0.5 {
+                return None;
+            }
+            if r > 0.4 {
+                return Some((255, 255));
+            }
+
+            let symbol = match power_up {
+                PowerUp::Shield => in_ellipse(u, v, 0.5, 0.42, 0.2, 0.16) || (v > 0.4 && (u - 0.5).abs() < (0.72 - v) * 0.7),
+                PowerUp::SlowMotion => (0.25..0.75).contains(&v) && (u - 0.5).abs() < (v - 0.5).abs() * 0.9,
+                PowerUp::Shrink => r < 0.12,
+                PowerUp::Magnet => {
+                    let bend = (r - 0.2).abs() < 0.06 && v > 0.5;
+                    let legs = distance_to_segment(u, v, 0.3, 0.3, 0.3, 0.5) < 0.06 || distance_to_segment(u, v, 0.7, 0.3, 0.7, 0.5) < 0.06;
+                    bend || legs
+                }
+            };
+
+            if symbol { Some((255, 255)) } else { Some((180, 110)) }
+        })
+    }
+
+    /// A crate with a torpedo painted on it
+    fn generate_ammo_texture(texture_creator: &'a TextureCreator<WindowContext>) -> Result<Texture<'a>, String> {
+        shape_texture(texture_creator, PICKUP_SIZE, PICKUP_SIZE, |u, v| {
+            let edge = !(0.1..0.9).contains(&u) || !(0.1..0.9).contains(&v);
+
+            if edge || in_ellipse(u, v, 0.5, 0.5, 0.3, 0.1) {
+                Some((60, 255))
+            } else {
+                Some((200, 255))
+            }
+        })
+    }
+
+    fn spawn(&mut self, rng: &mut impl Rng, x: f32, y: f32) {
+        let item = if rng.gen_bool(AMMO_CHANCE) {
+            Item::Ammo
+        } else {
+            Item::PowerUp(ALL_POWERUPS[rng.gen_range(0..ALL_POWERUPS.len())])
+        };
+
+        self.world.spawn(Components {
+            transform: Some(Transform { x, y, w: PICKUP_SIZE, h: PICKUP_SIZE }),
+            velocity: Some(Velocity { dx: 0.0, dy: 0.0, scroll: true }),
+            sprite: Some(Sprite::single(item.texture(), None, PICKUP_SIZE, PICKUP_SIZE)),
+            collider: Some(Collider { boxes: vec![Rect::new(0, 0, PICKUP_SIZE, PICKUP_SIZE)] }),
+            lifetime: Some(Lifetime::OffScreen),
+            data: Some(PickupData { item, base_y: y, phase: rng.gen_range(0.0..std::f32::consts::TAU) }),
+            ..Default::default()
+        });
+    }
+}
+
+impl<'a> Renderable for Pickups<'a> {
+    fn render(&self, _state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        ecs::render(&self.world, &self.textures, canvas)
+    }
+
+    fn update(&mut self, state: &GameState) {
+        let magnet = state.power_ups.active(PowerUp::Magnet);
+
+        for entity in self.world.entities().collect::<Vec<_>>() {
+            let (Some(t), Some(v), Some(pickup)) = (
+                &self.world.transforms[entity],
+                &mut self.world.velocities[entity],
+                &mut self.world.data[entity],
+            ) else {
+                continue;
+            };
+
+            // Pulled towards the sub when the magnet is active and we are close enough
+            if let (true, Some(player)) = (magnet, state.player) {
+                let dx = player.center().x() as f32 - (t.x + PICKUP_SIZE as f32 / 2.0);
+                let dy = player.center().y() as f32 - (t.y + PICKUP_SIZE as f32 / 2.0);
+                let distance = (dx * dx + dy * dy).sqrt();
+
+                if distance < MAGNET_RANGE && distance > 0.0 {
+                    pickup.base_y = t.y;
+                    v.scroll = false;
+                    v.dx = dx / distance * MAGNET_SPEED;
+                    v.dy = dy / distance * MAGNET_SPEED;
+                    continue;
+                }
+            }
+
+            // Otherwise, bob gently in the gap
+            pickup.phase += 0.08;
+            v.scroll = true;
+            v.dx = 0.0;
+            v.dy = pickup.base_y + pickup.phase.sin() * 6.0 - t.y;
+        }
+
+        ecs::movement(&mut self.world, state.x_speed as f32);
+        ecs::culling(&mut self.world);
+    }
+
+    fn switch_theme(&mut self, theme: &Theme) {
+        // Power-ups keep their own colors, so they are recognizable in every biome. Crates are painted like the sub.
+        self.textures[Item::Ammo.texture()].set_color_mod(theme.sub.0, theme.sub.1, theme.sub.2);
+    }
+
+    fn reset(&mut self) {
+        self.world.clear();
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.world.clone()))
+    }
+
+    fn restore(&mut self, snapshot: Box<dyn Any>) {
+        if let Ok(world) = snapshot.downcast() {
+            self.world = *world;
+        }
+    }
+
+    fn save(&self, w: &mut SaveWriter) {
+        self.world.save(w);
+    }
+
+    fn load(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        self.world = Save::load(r)?;
+        ecs::check_textures(&self.world, self.textures.len())?;
+        Ok(())
+    }
+
+    fn on_event(&mut self, event: &GameEvent, state: &GameState) {
+        if let GameEvent::PipeSpawned { x, gap_top, gap_bottom } = event {
+            let mut rng = state.rng();
+            if !rng.gen_bool(SPAWN_CHANCE) {
+                return;
+            }
+
+            // The pipe already moved since it spawned
+            let x = (x + 25 - state.x_speed) as f32 - PICKUP_SIZE as f32 / 2.0;
+            let y = (gap_top + gap_bottom) as f32 / 2.0 - PICKUP_SIZE as f32 / 2.0;
+            self.spawn(&mut *rng, x, y);
+        }
+    }
+
+    fn collect(&mut self, players: &[Rect], state: &GameState) {
+        for bb in players {
+            for entity in ecs::colliding(&self.world, *bb) {
+                match self.world.data[entity].as_ref().map(|p| p.item) {
+                    Some(Item::PowerUp(power_up)) => state.events.emit(GameEvent::PickedUp { power_up }),
+                    Some(Item::Ammo) => state.events.emit(GameEvent::AmmoCollected { count: AMMO_PER_CRATE }),
+                    None => {}
+                }
+                self.world.despawn(entity);
+            }
+        }
+    }
+}
+
+impl Save for PickupData {
+    fn save(&self, w: &mut SaveWriter) {
+        self.item.texture().save(w);
+        (self.base_y, self.phase).save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let item = match usize::load(r)? {
+            idx if idx < ALL_POWERUPS.len() => Item::PowerUp(ALL_POWERUPS[idx]),
+            _ => Item::Ammo,
+        };
+        let (base_y, phase) = Save::load(r)?;
+
+        Ok(Self { item, base_y, phase })
+    }
+}
@@ -1,41 +1,222 @@
+use std::any::Any;
 use sdl2::image::LoadTexture;
 use sdl2::render::{Texture, TextureCreator, WindowCanvas};
 use sdl2::video::WindowContext;
-use crate::{Collidable, GameState, Renderable};
+use crate::{Collidable, GameState, Renderable, PLAYER_X};
 use rand::Rng;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
+use crate::biome::{pick_weighted, Biome, PipeStyle};
+use crate::events::GameEvent;
+use crate::ecs::{self, Collider, Components, Lifetime, Scoring, Sprite, SpritePart, Transform, Velocity, World};
+use crate::save::{load_variant, save_variant, Save, SaveReader, SaveWriter};
+use crate::particles::{Emitter, EmitterConfig};
 use crate::theme::{Theme, THEME};
 
-struct Pipe {
-    x: i32,
+const PIPE_TEXTURE: usize = 0;
+const PIPE_END_TEXTURE: usize = 1;
+
+/// Width of a pipe
+const PIPE_WIDTH: u32 = 50;
+
+/// Chance that a pipe is rusty and can be broken by torpedoes
+const BREAKABLE_CHANCE: f64 = 0.3;
+
+/// Height of the part of a pipe that is blown off by a torpedo
+const SEGMENT_HEIGHT: i32 = 60;
+
+/// Pipes are never blown apart further than this from the edge of the screen
+const MIN_PIPE_HEIGHT: i32 = 30;
+
+/// Bonus points for blowing off a segment
+const SEGMENT_POINTS: i64 = 25;
+
+/// Distance the gap of an oscillating pipe moves up and down
+const OSCILLATION: f32 = 40.0;
+
+/// Valves close down to this fraction of their gap
+const VALVE_CLOSED: f32 = 0.7;
+
+/// Height of the slices slanted pipes are made of
+const SLICE_HEIGHT: i32 = 20;
+
+/// Upward force of a vent on the sub, and how many ticks a vent blows and rests
+const VENT_FORCE: f32 = 0.45;
+const VENT_ON: u32 = 120;
+const VENT_OFF: u32 = 60;
+
+/// The different kinds of pipes
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Variant {
+    /// Plain pair of pipes
+    Static,
+    /// The gap moves up and down
+    Oscillating,
+    /// The gap opens and closes
+    Valve,
+    /// Both pipes lean over
+    Slanted,
+    /// A jet of bubbles blows up from the bottom pipe, pushing the sub up
+    Vent,
+    /// The pipe ends are broken off and leak
+    Leaking,
+}
+
+const ALL_VARIANTS: [Variant; 6] = [
+    Variant::Static,
+    Variant::Oscillating,
+    Variant::Valve,
+    Variant::Slanted,
+    Variant::Vent,
+    Variant::Leaking,
+];
+
+/// Names of all pipe variants, as used in the biome files
+pub fn variant_names() -> Vec<&'static str> {
+    ALL_VARIANTS.iter().map(|v| v.name()).collect()
+}
+
+impl Variant {
+    fn from_name(name: &str) -> Option<Self> {
+        ALL_VARIANTS.iter().copied().find(|v| v.name() == name)
+    }
+
+    /// Name as used in the biome files
+    fn name(&self) -> &'static str {
+        match self {
+            Variant::Static => "static",
+            Variant::Oscillating => "oscillating",
+            Variant::Valve => "valve",
+            Variant::Slanted => "slanted",
+            Variant::Vent => "vent",
+            Variant::Leaking => "leaking",
+        }
+    }
+}
+
+/// Pipe specific component
+#[derive(Clone)]
+struct PipeData {
+    variant: Variant,
+    // Current gap
     top_offset: i32,
     bottom_offset: i32,
+    // Gap around which oscillating pipes and valves move
+    gap_center: f32,
+    gap_size: f32,
+    // Ticks since the pipe appeared
+    ticks: u32,
+    // Horizontal pixels per vertical pixel that slanted pipes lean over
+    slope: f32,
+    // Barnacles (x, y offset from the pipe opening) on encrusted pipes
+    barnacles: Vec<(i32, i32)>,
+    // Rusty pipes can be blown apart by torpedoes, one segment at a time
+    breakable: bool,
 }
 
-impl Pipe {
-    fn new(x: i32, top_offset: i32, bottom_offset: i32) -> Self {
-        Self {
-            x,
-            top_offset,
-            bottom_offset,
-        }
+impl PipeData {
+    fn venting(&self) -> bool {
+        self.variant == Variant::Vent && self.ticks % (VENT_ON + VENT_OFF) < VENT_ON
     }
+}
 
-    fn finished(&self) -> bool {
-        self.x < -50.0 as i32
+/// Sizes of the pipe textures, as they are drawn
+#[derive(Clone, Copy)]
+struct PipeSizes {
+    body_height: u32,
+    end: (u32, u32),
+}
+
+/// Sprite and collider of a pipe, depending on its variant and current gap
+fn shape(pipe: &PipeData, sizes: PipeSizes) -> (Sprite, Collider) {
+    let part = |texture, dst, flip_vertical| SpritePart { texture, src: None, dst, flip_horizontal: false, flip_vertical };
+    let (top, bottom) = (pipe.top_offset, pipe.bottom_offset);
+    let (end_w, end_h) = sizes.end;
+
+    let mut parts = vec![];
+    let mut boxes = vec![];
+
+    match pipe.variant {
+        Variant::Slanted => {
+            // Made of slices that shift sideways the further they are from the gap
+            for y in (0..top).step_by(SLICE_HEIGHT as usize) {
+                let h = SLICE_HEIGHT.min(top - y);
+                let dx = ((y + h / 2 - top) as f32 * pipe.slope) as i32;
+                parts.push(part(PIPE_TEXTURE, Rect::new(dx, y, PIPE_WIDTH, h as u32), true));
+                boxes.push(Rect::new(dx - 5, y, 60, h as u32));
+            }
+            for y in (bottom..bottom + sizes.body_height as i32).step_by(SLICE_HEIGHT as usize) {
+                let dx = ((y + SLICE_HEIGHT / 2 - bottom) as f32 * pipe.slope) as i32;
+                parts.push(part(PIPE_TEXTURE, Rect::new(dx, y, PIPE_WIDTH, SLICE_HEIGHT as u32), false));
+                boxes.push(Rect::new(dx - 5, y, 60, SLICE_HEIGHT as u32));
+            }
+
+            parts.push(part(PIPE_END_TEXTURE, Rect::new(-7, top - 10, end_w, end_h), true));
+            parts.push(part(PIPE_END_TEXTURE, Rect::new(-7, bottom, end_w, end_h), false));
+            boxes.push(Rect::new(-5, top - 10, 60, 30));
+        }
+        Variant::Leaking => {
+            // Ends broken off, so only the (narrower) bodies remain
+            parts.push(part(PIPE_TEXTURE, Rect::new(0, 0, PIPE_WIDTH, top as u32), true));
+            parts.push(part(PIPE_TEXTURE, Rect::new(0, bottom, PIPE_WIDTH, sizes.body_height - bottom as u32), false));
+            boxes.push(Rect::new(0, 0, PIPE_WIDTH, top as u32));
+            boxes.push(Rect::new(0, bottom, PIPE_WIDTH, 600));
+        }
+        _ => {
+            // Top pipe
+            parts.push(part(PIPE_TEXTURE, Rect::new(0, 0, PIPE_WIDTH, top as u32), true));
+            parts.push(part(PIPE_END_TEXTURE, Rect::new(-7, top - 10, end_w, end_h), true));
+            // Bottom pipe
+            parts.push(part(PIPE_TEXTURE, Rect::new(0, bottom, PIPE_WIDTH, sizes.body_height - bottom as u32), false));
+            parts.push(part(PIPE_END_TEXTURE, Rect::new(-7, bottom, end_w, end_h), false));
+
+            boxes.push(Rect::new(-5, 0, 60, top as u32 + 20));
+            boxes.push(Rect::new(-5, bottom, 60, 600));
+        }
     }
 
-    fn update(&mut self, speed: i32) {
-        self.x -= speed;
+    (Sprite { parts }, Collider { boxes })
+}
+
+/// Variant system: moves the gaps of oscillating pipes and valves
+fn variants(world: &mut World<PipeData>, sizes: PipeSizes) {
+    for entity in world.entities().collect::<Vec<_>>() {
+        let (Some(t), Some(pipe)) = (world.transforms[entity], world.data[entity].as_mut()) else {
+            continue;
+        };
+
+        pipe.ticks += 1;
+        let phase = pipe.ticks as f32 / 60.0;
+
+        let (center, size) = match pipe.variant {
+            Variant::Oscillating => (pipe.gap_center + (phase * 2.0).sin() * OSCILLATION, pipe.gap_size),
+            Variant::Valve => {
+                let open = VALVE_CLOSED + (1.0 - VALVE_CLOSED) * ((phase * 2.4).sin() + 1.0) / 2.0;
+                (pipe.gap_center, pipe.gap_size * open)
+            }
+            _ => continue,
+        };
+
+        // Keep some pipe on both sides
+        let center = center.clamp(size / 2.0 + MIN_PIPE_HEIGHT as f32, t.h as f32 - size / 2.0 - MIN_PIPE_HEIGHT as f32);
+        pipe.top_offset = (center - size / 2.0) as i32;
+        pipe.bottom_offset = (center + size / 2.0) as i32;
+
+        let (sprite, collider) = shape(pipe, sizes);
+        world.sprites[entity] = Some(sprite);
+        world.colliders[entity] = Some(collider);
     }
 }
 
 pub struct Pipes<'a> {
-    pipe_texture: Texture<'a>,
-    pipe_end_texture: Texture<'a>,
-    pipes: Vec<Pipe>,
+    textures: Vec<Texture<'a>>,
+    sizes: PipeSizes,
+    world: World<PipeData>,
     next_pipe_at: i64,
+    // Bubble jets of the vents
+    jets: Emitter<'a>,
+    // Water leaking from broken pipes
+    leaks: Emitter<'a>,
 }
 
 impl<'a> Pipes<'a> {
@@ -46,65 +227,138 @@ impl<'a> Pipes<'a> {
         pipe_texture.set_color_mod(THEME.pipes.0, THEME.pipes.1, THEME.pipes.2);
         pipe_end_texture.set_color_mod(THEME.pipes.0, THEME.pipes.1, THEME.pipes.2);
 
+        let q = pipe_texture.query();
+        let qe = pipe_end_texture.query();
+        let sizes = PipeSizes {
+            body_height: q.height + 50,
+            end: (qe.width - 20, qe.height - 10),
+        };
+
+        let jets = Emitter::new(EmitterConfig {
+            max_particles: 300,
+            lifetime: 20..35,
+            velocity_x: -0.5..0.5,
+            velocity_y: -6.0..-4.0,
+            drag: 0.98,
+            size: (4.0, 2.0),
+            alpha: (220, 0),
+            ..Default::default()
+        });
+
+        let leaks = Emitter::new(EmitterConfig {
+            max_particles: 200,
+            lifetime: 30..60,
+            velocity_x: -1.0..1.0,
+            velocity_y: -0.5..0.5,
+            buoyancy: -0.05,
+            size: (3.0, 1.0),
+            alpha: (200, 0),
+            ..Default::default()
+        });
 
         Self {
-            pipe_texture: pipe_texture,
-            pipe_end_texture: pipe_end_texture,
-            pipes: Vec::new(),
+            textures: vec![pipe_texture, pipe_end_texture],
+            sizes,
+            world: World::new(),
             next_pipe_at: 0,
+            jets,
+            leaks,
         }
     }
 
-    fn generate(&self, x: i32, height: u32) -> Pipe {
-        let mut rng = rand::thread_rng();
+    fn generate(&mut self, rng: &mut impl Rng, x: i32, height: u32, biome: &Biome, gap_scale: f32) -> (i32, i32) {
 
-        let hole_size = rng.gen_range(150..250);
+        // Leave room for the pipe ends and the margins above and below the hole
+        let hole_size = (rng.gen_range(150..250) as f32 * biome.gap_scale * gap_scale) as u32;
+        let hole_size = hole_size.min(height.saturating_sub(2 * (50 + MIN_PIPE_HEIGHT as u32)));
         let hole_offset = rng.gen_range(50..height - hole_size - 50);
 
-        Pipe::new(x, hole_offset as i32, (hole_offset + hole_size) as i32)
+        let table: Vec<(Variant, u32)> = biome.pipe_variants.iter()
+            .filter_map(|(name, weight)| Variant::from_name(name).map(|v| (v, *weight)))
+            .collect();
+        let variant = pick_weighted(rng, &table).unwrap_or(Variant::Static);
+
+        let mut barnacles = vec![];
+        if biome.pipe_style == PipeStyle::Barnacles && variant != Variant::Slanted {
+            for _ in 0..rng.gen_range(4..12) {
+                // Negative y are on the top pipe, positive on the bottom pipe
+                let y = rng.gen_range(15..120);
+                barnacles.push((rng.gen_range(0..46), if rng.gen_bool(0.5) { -y } else { y }));
+            }
+        }
+
+        let slope = if variant == Variant::Slanted {
+            rng.gen_range(0.15..0.35) * if rng.gen_bool(0.5) { -1.0 } else { 1.0 }
+        } else {
+            0.0
+        };
+
+        let pipe = PipeData {
+            variant,
+            top_offset: hole_offset as i32,
+            bottom_offset: (hole_offset + hole_size) as i32,
+            gap_center: hole_offset as f32 + hole_size as f32 / 2.0,
+            gap_size: hole_size as f32,
+            ticks: 0,
+            slope,
+            barnacles,
+            breakable: variant == Variant::Static && rng.gen_bool(BREAKABLE_CHANCE),
+        };
+        let (sprite, collider) = shape(&pipe, self.sizes);
+        let gap = (pipe.top_offset, pipe.bottom_offset);
+
+        // Slanted pipes stick out to the right, and should not be removed while still visible
+        let width = PIPE_WIDTH + (slope.abs() * height as f32) as u32;
+
+        self.world.spawn(Components {
+            transform: Some(Transform { x: x as f32, y: 0.0, w: width, h: height }),
+            velocity: Some(Velocity { dx: 0.0, dy: 0.0, scroll: true }),
+            sprite: Some(sprite),
+            collider: Some(collider),
+            lifetime: Some(Lifetime::OffScreen),
+            scoring: Some(Scoring { points: 1, passed: false }),
+            data: Some(pipe),
+        });
+
+        gap
     }
 }
 
 impl<'a> Renderable for Pipes<'a> {
-    fn render(&self, _state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
-        let q = self.pipe_texture.query();
-        let qe = self.pipe_end_texture.query();
+    fn render(&self, state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let theme = state.theme.current();
 
-        // render each pipe
-        for pipe in &self.pipes {
-            // Top pipe
-            canvas.copy_ex(
-                &self.pipe_texture,
-                None,
-                Rect::new(pipe.x, 0, 50, pipe.top_offset as u32),
-                0.0,
-                None,
-                false,
-                true
-            )?;
-
-            canvas.copy_ex(
-                &self.pipe_end_texture,
-                None,
-                Rect::new(pipe.x - 7, pipe.top_offset - 10, qe.width - 20, qe.height - 10),
-                0.0,
-                None,
-                false,
-                true
-            )?;
+        self.leaks.render(canvas, theme.bubbles)?;
+        ecs::render(&self.world, &self.textures, canvas)?;
+        self.jets.render(canvas, theme.bubbles)?;
 
-            // Bottom pipe
-            canvas.copy(
-                &self.pipe_texture,
-                None,
-                Rect::new(pipe.x, pipe.bottom_offset, 50, q.height + 50 - pipe.bottom_offset as u32),
-            )?;
-
-            canvas.copy(
-                &self.pipe_end_texture,
-                None,
-                Rect::new(pipe.x - 7, pipe.bottom_offset, qe.width - 20, qe.height - 10),
-            )?;
+        // Barnacles
+        canvas.set_draw_color(Color::RGB(theme.sand_highlight.0, theme.sand_highlight.1, theme.sand_highlight.2));
+        for entity in self.world.entities() {
+            let (Some(t), Some(pipe)) = (self.world.transforms[entity], self.world.data[entity].as_ref()) else {
+                continue;
+            };
+
+            for (bx, by) in &pipe.barnacles {
+                let y = if *by < 0 { pipe.top_offset + by } else { pipe.bottom_offset + by };
+                canvas.fill_rect(Rect::new(t.x as i32 + bx, y, 4, 3))?;
+            }
+        }
+
+        // Rust stripes on breakable pipes, one on every segment
+        canvas.set_draw_color(Color::RGB(150, 80, 40));
+        for entity in self.world.entities() {
+            let (Some(t), Some(pipe)) = (self.world.transforms[entity], self.world.data[entity].as_ref()) else {
+                continue;
+            };
+            if !pipe.breakable {
+                continue;
+            }
+
+            for i in 1..4 {
+                canvas.fill_rect(Rect::new(t.x as i32 + 4, pipe.top_offset - i * SEGMENT_HEIGHT + 20, PIPE_WIDTH - 8, 3))?;
+                canvas.fill_rect(Rect::new(t.x as i32 + 4, pipe.bottom_offset + i * SEGMENT_HEIGHT - 20, PIPE_WIDTH - 8, 3))?;
+            }
         }
 
         // // Draw bounding boxes
@@ -117,47 +371,170 @@ impl<'a> Renderable for Pipes<'a> {
     }
 
     fn update(&mut self, state: &GameState) {
-        let mut rng = rand::thread_rng();
-
         // Add a new pipe when it's time for one
-        if state.fc > self.next_pipe_at {
-            // New pipe
-            let new_pipe = self.generate(state.window_width as i32, state.window_height);
-            self.pipes.push(new_pipe);
+        if state.mode.dangers() && state.fc > self.next_pipe_at {
+            let mut rng = state.rng();
+            let x = state.window_width as i32;
+            let (gap_top, gap_bottom) = self.generate(&mut *rng, x, state.window_height, state.theme.biome(), state.mode.gap_scale());
+            state.events.emit(GameEvent::PipeSpawned { x, gap_top, gap_bottom });
 
             self.next_pipe_at = state.fc + rng.gen_range(75..200);
         }
 
-        // Move all the pipes
-        for pipe in self.pipes.iter_mut() {
-            pipe.update(state.x_speed);
+        // Move all the pipes, and remove the ones that are off-screen
+        variants(&mut self.world, self.sizes);
+        ecs::movement(&mut self.world, state.x_speed as f32);
+        ecs::culling(&mut self.world);
+        for entity in self.world.entities() {
+            if let (Some(t), Some(pipe)) = (self.world.transforms[entity], self.world.data[entity].as_ref()) {
+                if matches!(pipe.variant, Variant::Oscillating | Variant::Valve) {
+                    state.events.emit(GameEvent::PipeMoved { x: t.x as i32, gap_top: pipe.top_offset, gap_bottom: pipe.bottom_offset });
+                }
+            }
         }
 
-        // Remove pipes that are off-screen
-        self.pipes.retain(|pipe| !pipe.finished());
+        // Vents and leaks
+        let mut rng = rand::thread_rng();
+        for entity in self.world.entities() {
+            let (Some(t), Some(pipe)) = (self.world.transforms[entity], self.world.data[entity].as_ref()) else {
+                continue;
+            };
+
+            if pipe.venting() {
+                self.jets.set_spawn_area(Rect::new(t.x as i32 + 5, pipe.bottom_offset, PIPE_WIDTH - 10, 4));
+                self.jets.burst(2);
+            }
+            if pipe.variant == Variant::Leaking && rng.gen_bool(0.5) {
+                let y = if rng.gen_bool(0.5) { pipe.top_offset - 4 } else { pipe.bottom_offset };
+                self.leaks.set_spawn_area(Rect::new(t.x as i32, y, PIPE_WIDTH, 4));
+                self.leaks.burst(1);
+            }
+        }
+        self.jets.update(state.x_speed as f32);
+        self.leaks.update(state.x_speed as f32);
+
+        if !state.game_over {
+            for _ in 0..ecs::scoring(&mut self.world, PLAYER_X) {
+                state.events.emit(GameEvent::PipePassed);
+            }
+        }
     }
 
     fn switch_theme(&mut self, theme: &Theme) {
-        self.pipe_texture.set_color_mod(theme.pipes.0, theme.pipes.1, theme.pipes.2);
-        self.pipe_end_texture.set_color_mod(theme.pipes.0, theme.pipes.1, theme.pipes.2);
+        for texture in self.textures.iter_mut() {
+            texture.set_color_mod(theme.pipes.0, theme.pipes.1, theme.pipes.2);
+        }
+    }
+
+    fn as_collidable(&self) -> Option<&dyn Collidable> {
+        Some(self)
+    }
+
+    fn force_at(&self, area: Rect) -> (f32, f32) {
+        let venting = self.world.entities().any(|entity| {
+            let (Some(t), Some(pipe)) = (self.world.transforms[entity], self.world.data[entity].as_ref()) else {
+                return false;
+            };
+
+            let jet = Rect::new(t.x as i32, pipe.top_offset, PIPE_WIDTH, (pipe.bottom_offset - pipe.top_offset) as u32);
+            pipe.venting() && jet.has_intersection(area)
+        });
+
+        if venting { (0.0, -VENT_FORCE) } else { (0.0, 0.0) }
+    }
+
+    fn hit(&mut self, area: Rect, state: &GameState) -> bool {
+        let hit = ecs::colliding(&self.world, area);
+
+        for entity in &hit {
+            let (Some(t), Some(pipe)) = (self.world.transforms[*entity], self.world.data[*entity].as_mut()) else {
+                continue;
+            };
+            if !pipe.breakable {
+                continue;
+            }
+
+            // Blow off the segment at the end of the pipe that was hit. Stubs cannot be broken, so once both ends are
+            // down to a stub the pipe stops scoring.
+            let (top, bottom) = (MIN_PIPE_HEIGHT, t.h as i32 - MIN_PIPE_HEIGHT);
+            let (old_top, old_bottom) = (pipe.top_offset, pipe.bottom_offset);
+            if area.center().y() < (pipe.top_offset + pipe.bottom_offset) / 2 {
+                pipe.top_offset = (pipe.top_offset - SEGMENT_HEIGHT).max(top).min(old_top);
+            } else {
+                pipe.bottom_offset = (pipe.bottom_offset + SEGMENT_HEIGHT).min(bottom).max(old_bottom);
+            }
+            let broken = (pipe.top_offset, pipe.bottom_offset) != (old_top, old_bottom);
+            let stripped = pipe.top_offset <= top && pipe.bottom_offset >= bottom;
+            if !broken && !stripped {
+                continue;
+            }
+            pipe.breakable = !stripped;
+            if broken {
+                pipe.barnacles.clear();
+                state.events.emit(GameEvent::Destroyed { points: SEGMENT_POINTS });
+                state.events.emit(GameEvent::PipeMoved { x: t.x as i32, gap_top: pipe.top_offset, gap_bottom: pipe.bottom_offset });
+            }
+
+            let (sprite, collider) = shape(pipe, self.sizes);
+            self.world.sprites[*entity] = Some(sprite);
+            self.world.colliders[*entity] = Some(collider);
+        }
+
+        !hit.is_empty()
     }
 
     fn reset(&mut self) {
-        self.pipes.clear();
+        self.world.clear();
         self.next_pipe_at = 0;
     }
+
+    fn snapshot(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new((self.world.clone(), self.next_pipe_at)))
+    }
+
+    fn restore(&mut self, snapshot: Box<dyn Any>) {
+        if let Ok(state) = snapshot.downcast::<(World<PipeData>, i64)>() {
+            (self.world, self.next_pipe_at) = *state;
+        }
+    }
+
+    fn save(&self, w: &mut SaveWriter) {
+        self.world.save(w);
+        self.next_pipe_at.save(w);
+    }
+
+    fn load(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        self.world = Save::load(r)?;
+        ecs::check_textures(&self.world, self.textures.len())?;
+        self.next_pipe_at = Save::load(r)?;
+        Ok(())
+    }
 }
 
 impl<'a> Collidable for Pipes<'a> {
     fn get_bounding_boxes(&self) -> Vec<Rect> {
-        let mut boxes = vec![];
+        ecs::bounding_boxes(&self.world)
+    }
+}
 
-        for pipe in &self.pipes {
-            boxes.push(Rect::new(pipe.x - 5, 0, 60, pipe.top_offset as u32 + 20));
-            boxes.push(Rect::new(pipe.x - 5, pipe.bottom_offset,60, 600));
+impl Save for PipeData {
+    fn save(&self, w: &mut SaveWriter) {
+        save_variant(&ALL_VARIANTS, &self.variant, w);
+        (self.top_offset, self.bottom_offset).save(w);
+        (self.gap_center, self.gap_size).save(w);
+        (self.ticks, self.slope).save(w);
+        self.barnacles.save(w);
+        self.breakable.save(w);
+    }
 
-        }
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let variant = load_variant(&ALL_VARIANTS, r)?;
+        let (top_offset, bottom_offset) = Save::load(r)?;
+        let (gap_center, gap_size) = Save::load(r)?;
+        let (ticks, slope) = Save::load(r)?;
+        let barnacles = Save::load(r)?;
+        let breakable = Save::load(r)?;
 
-        boxes
+        Ok(Self { variant, top_offset, bottom_offset, gap_center, gap_size, ticks, slope, barnacles, breakable })
     }
-}
\ No newline at end of file
+}
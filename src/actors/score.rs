@@ -2,6 +2,9 @@ use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::WindowCanvas;
 use crate::{GameState, Renderable, TTF};
+use crate::oxygen::MAX_OXYGEN;
+use crate::powerup::ALL_POWERUPS;
+use crate::rewind::RewindMode;
 use crate::theme::Theme;
 
 pub struct Score<'a> {
@@ -12,6 +15,88 @@ impl<'a> Score<'a> {
     pub fn new(ttf: &'a TTF) -> Self {
         Self { ttf }
     }
+
+    /// Oxygen gauge below the biome name. Turns red when running low.
+    fn render_oxygen(&self, state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let theme = state.theme.current();
+        let gauge = Rect::new(state.window_width as i32 - 170, 46, 150, 12);
+
+        let level = state.oxygen.level() / MAX_OXYGEN;
+        let color = if level < 0.25 { (220, 60, 50) } else { theme.bubbles };
+
+        canvas.set_draw_color(Color::RGB(color.0, color.1, color.2));
+        canvas.fill_rect(Rect::new(gauge.x(), gauge.y(), (gauge.width() as f32 * level) as u32, gauge.height()))?;
+
+        canvas.set_draw_color(Color::RGB(theme.text.0, theme.text.1, theme.text.2));
+        canvas.draw_rect(gauge)?;
+
+        let surface = self.ttf.font
+            .render("O2")
+            .blended(Color::RGBA(theme.text.0, theme.text.1, theme.text.2, 255))
+            .map_err(|e| e.to_string())?;
+
+        let creator = canvas.texture_creator();
+        let texture = creator
+            .create_texture_from_surface(&surface)
+            .map_err(|e| e.to_string())?;
+
+        let width = surface.width() * 16 / surface.height();
+        canvas.copy(&texture, None, Rect::new(gauge.x() - width as i32 - 8, gauge.y() - 2, width, 16))?;
+
+        Ok(())
+    }
+
+    /// Tells the player they can rewind after a crash, and shows when they are rewinding
+    fn render_rewind(&self, state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let s = match state.rewind.mode {
+            RewindMode::Off => return Ok(()),
+            RewindMode::Prompt => "Hold <r> to rewind",
+            RewindMode::Rewinding => "<< Rewind",
+        };
+
+        let theme = state.theme.current();
+        let surface = self.ttf.font
+            .render(s)
+            .blended(Color::RGBA(theme.text.0, theme.text.1, theme.text.2, 255))
+            .map_err(|e| e.to_string())?;
+
+        let creator = canvas.texture_creator();
+        let texture = creator
+            .create_texture_from_surface(&surface)
+            .map_err(|e| e.to_string())?;
+
+        let width = surface.width() * 40 / surface.height();
+        canvas.copy(&texture, None, Rect::new((state.window_width - width) as i32 / 2, 120, width, 40))?;
+
+        Ok(())
+    }
+
+    /// Score of every player in versus, at the bottom of the screen
+    fn render_players(&self, state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let s = (0..state.players)
+            .map(|p| {
+                let sunk = if state.sunk_at[p].is_some() { " (sunk)" } else { "" };
+                format!("P{}: {:06}{}", p + 1, state.player_score(p), sunk)
+            })
+            .collect::<Vec<_>>()
+            .join("   ");
+
+        let theme = state.theme.current();
+        let surface = self.ttf.font
+            .render(s.as_str())
+            .blended(Color::RGBA(theme.text.0, theme.text.1, theme.text.2, 255))
+            .map_err(|e| e.to_string())?;
+
+        let creator = canvas.texture_creator();
+        let texture = creator
+            .create_texture_from_surface(&surface)
+            .map_err(|e| e.to_string())?;
+
+        let width = surface.width() * 24 / surface.height();
+        canvas.copy(&texture, None, Rect::new(20, state.window_height as i32 - 34, width, 24))?;
+
+        Ok(())
+    }
 }
 
 impl<'a> Renderable for Score<'a> {
@@ -20,7 +105,7 @@ impl<'a> Renderable for Score<'a> {
 
         // Print score
         let surface = self.ttf.font
-            .render(format!("Score: {:06}   Hi-Score: {:06}", state.fc, state.high_score).as_str())
+            .render(format!("Score: {:06}   Hi-Score: {:06}", state.score(), state.high_score()).as_str())
             .blended(Color::RGBA(theme.text.0, theme.text.1, theme.text.2, 255))
             .map_err(|e| e.to_string())?;
 
@@ -31,6 +116,81 @@ impl<'a> Renderable for Score<'a> {
 
         canvas.copy(&texture, None, Rect::new(20, 10, 300, 30))?;
 
+        // Print pipes passed, ammo and rewind time, and the lives left when practicing
+        let mut s = format!("Pipes: {}   Torpedoes: {}", state.pipes, state.torpedoes);
+        if state.can_rewind() {
+            s += &format!("   Rewind: {}s", state.rewind.budget() / 60);
+        }
+        if let Some(net) = &state.net {
+            s += &format!("   Race: {} racers", net.racers());
+        }
+        if state.daily.is_some() {
+            s += "   Daily challenge";
+        }
+        if let Some(limit) = state.mode.time_limit() {
+            s += &format!("   Time: {}s", (limit - state.fc).max(0) / 60);
+        }
+        if state.practice {
+            s += &format!("   Practice - Lives: {}   Checkpoint: {}", state.lives, state.checkpoint());
+        }
+        let surface = self.ttf.font
+            .render(s.as_str())
+            .blended(Color::RGBA(theme.text.0, theme.text.1, theme.text.2, 255))
+            .map_err(|e| e.to_string())?;
+
+        let texture = creator
+            .create_texture_from_surface(&surface)
+            .map_err(|e| e.to_string())?;
+
+        let width = surface.width() * 20 / surface.height();
+        canvas.copy(&texture, None, Rect::new(20, 40, width, 20))?;
+
+        // Active power-ups with a bar showing how long they last
+        let mut y = 66;
+        for power_up in ALL_POWERUPS.iter().filter(|p| state.power_ups.active(**p)) {
+            let color = power_up.color();
+
+            let surface = self.ttf.font
+                .render(power_up.name())
+                .blended(Color::RGBA(color.0, color.1, color.2, 255))
+                .map_err(|e| e.to_string())?;
+
+            let texture = creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())?;
+
+            let width = surface.width() * 16 / surface.height();
+            canvas.copy(&texture, None, Rect::new(20, y, width, 16))?;
+
+            let remaining = state.power_ups.remaining(*power_up) * 100 / power_up.duration();
+            canvas.set_draw_color(Color::RGB(color.0, color.1, color.2));
+            canvas.fill_rect(Rect::new(90, y + 4, remaining.max(1), 8))?;
+
+            y += 20;
+        }
+
+        // Print current biome
+        let surface = self.ttf.font
+            .render(state.theme.biome().name.as_str())
+            .blended(Color::RGBA(theme.text.0, theme.text.1, theme.text.2, 255))
+            .map_err(|e| e.to_string())?;
+
+        let texture = creator
+            .create_texture_from_surface(&surface)
+            .map_err(|e| e.to_string())?;
+
+        // Keep the same text height as the score, and scale the width accordingly
+        let width = surface.width() * 30 / surface.height();
+        canvas.copy(&texture, None, Rect::new(state.window_width as i32 - width as i32 - 20, 10, width, 30))?;
+
+        self.render_oxygen(state, canvas)?;
+        if state.can_rewind() {
+            self.render_rewind(state, canvas)?;
+        }
+        if state.players > 1 {
+            self.render_players(state, canvas)?;
+        }
+
         Ok(())
     }
 
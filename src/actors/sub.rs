@@ -1,47 +1,269 @@
-use sdl2::image::LoadTexture;
-use sdl2::pixels::Color;
+use std::any::Any;
+use std::cell::RefCell;
 use sdl2::rect::Rect;
-use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::render::{BlendMode, Texture, TextureCreator, WindowCanvas};
 use sdl2::video::WindowContext;
-use crate::{Collidable, GameState, Renderable};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use crate::{Collidable, GameState, Renderable, MAX_PLAYERS};
+use crate::animation::{load_sprite, Animation, AnimationPlayer};
+use crate::events::GameEvent;
+use crate::particles::{Emitter, EmitterConfig, ParticleShape};
+use crate::physics::{ControlMode, Physics};
+use crate::powerup::PowerUp;
+use crate::procedural::shape_texture;
+use crate::save::{Save, SaveReader, SaveWriter};
 use crate::theme::Theme;
 
 /// Submarine drawable object
 pub struct Sub<'a> {
+    // Number of the player steering this sub, starting at 0
+    number: usize,
+    // True when the sub takes part in the run, as not every run has all players
+    playing: bool,
+    // True when the sub crashed, and is out of the run
+    sunk: bool,
     initial_x: i32,
-    initial_y: i32,
+    initial_y: f32,
     x: i32,
-    y: i32,
+    y: f32,
     angle: f32,
-    pub velocity: f32,
-    gravity: f32,
-    pub jump_strength: f32,
+    velocity: f32,
+    // Horizontal offset from x where currents pushed the sub, and how fast it drifts
+    drift: f32,
+    drift_velocity: f32,
+    // True while the ballast tanks are blown (ballast control mode)
+    blowing: bool,
     texture: Texture<'a>,
+    animation: Animation,
+    player: AnimationPlayer,
+    // Propeller wake behind the sub
+    wake: Emitter<'a>,
+    // Burst of bubbles when diving
+    dive_bubbles: Emitter<'a>,
+    // Wreckage after a crash
+    debris: Emitter<'a>,
+    // Glow around the sub for power-ups. Tinted while rendering, hence the RefCell.
+    aura: RefCell<Texture<'a>>,
+    // Ticks the sub cannot crash after the shield absorbed a collision
+    grace: u32,
+    // Bounding box as of the last update
+    hitbox: Rect,
 }
 
+/// Everything that moves the sub, without the textures and particles
+#[derive(Clone)]
+struct SubState {
+    y: f32,
+    angle: f32,
+    velocity: f32,
+    drift: f32,
+    drift_velocity: f32,
+    blowing: bool,
+    grace: u32,
+    hitbox: Rect,
+    sunk: bool,
+}
+
+/// Width and height of the sub on screen
+pub const SUB_SIZE: (u32, u32) = (50, 45);
+
+/// Scale of the sub when the shrink power-up is active
+const SHRUNK: f32 = 0.6;
+
+/// Ticks the sub cannot crash after the shield absorbed a collision, or after respawning in practice mode
+const SHIELD_GRACE: u32 = 60;
+const RESPAWN_GRACE: u32 = 120;
+
+/// Currents can push the sub at most this far from its spot, and the propeller pulls it back this fast
+const MAX_DRIFT: f32 = 80.0;
+const DRIFT_RECOVERY: f32 = 0.02;
+
+/// Dive and fire keys of every player, as they share the keyboard
+pub const CONTROLS: [(Keycode, Keycode); MAX_PLAYERS] = [
+    (Keycode::Space, Keycode::F),
+    (Keycode::Up, Keycode::Down),
+];
+
+/// Tint on top of the color of the biome, so players can tell their subs apart
+const TINTS: [(u8, u8, u8); MAX_PLAYERS] = [
+    (255, 255, 255),
+    (255, 150, 100),
+];
+
 impl<'a> Sub<'a> {
-    pub fn new(x: i32, y: i32, texture_creator: &'a TextureCreator<WindowContext>) -> Self {
-        let texture = texture_creator.load_texture("images/sub-large.png").unwrap();
+    pub fn new(number: usize, x: i32, y: i32, texture_creator: &'a TextureCreator<WindowContext>) -> Self {
+        let (texture, animation) = load_sprite(texture_creator, "images/sub-large.png").unwrap();
+
+        let wake = Emitter::new(EmitterConfig {
+            rate: 0.6,
+            lifetime: 20..45,
+            velocity_x: -1.5..-0.5,
+            velocity_y: -0.3..0.3,
+            drag: 0.96,
+            buoyancy: -0.02,
+            size: (4.0, 1.0),
+            alpha: (200, 0),
+            ..Default::default()
+        });
+
+        let bubble_sprites = ["images/bubble-sm.png", "images/bubble-md.png"]
+            .iter()
+            .map(|path| load_sprite(texture_creator, path).unwrap())
+            .collect();
+        let dive_bubbles = Emitter::with_textures(EmitterConfig {
+            lifetime: 20..50,
+            velocity_x: -2.0..0.0,
+            velocity_y: -2.0..0.5,
+            drag: 0.95,
+            buoyancy: -0.08,
+            size: (0.6, 1.0),
+            shape: ParticleShape::Textures,
+            ..Default::default()
+        }, bubble_sprites);
+
+        let debris = Emitter::new(EmitterConfig {
+            lifetime: 60..120,
+            velocity_x: -3.0..3.0,
+            velocity_y: -4.0..1.0,
+            drag: 0.97,
+            buoyancy: 0.12,
+            size: (5.0, 3.0),
+            alpha: (255, 120),
+            ..Default::default()
+        });
+
+        let mut aura = shape_texture(texture_creator, 64, 64, |u, v| {
+            let (dx, dy) = (u - 0.5, v - 0.5);
+            let r = (dx * dx + dy * dy).sqrt();
+
+            if r < 0.5 { Some((255, (r * r * 4.0 * 255.0) as u8)) } else { None }
+        }).unwrap();
+        aura.set_blend_mode(BlendMode::Blend);
 
         Sub {
+            number,
+            playing: number == 0,
+            sunk: false,
             initial_x: x,
-            initial_y: y,
+            initial_y: y as f32,
             x,
-            y,
+            y: y as f32,
             angle: 0.0,
             velocity: 0.0,
-            gravity: 0.2,
-            jump_strength: -5.0,
+            drift: 0.0,
+            drift_velocity: 0.0,
+            blowing: false,
             texture,
+            animation,
+            player: AnimationPlayer::new(),
+            wake,
+            dive_bubbles,
+            debris,
+            aura: RefCell::new(aura),
+            grace: 0,
+            hitbox: Rect::new(x, y, SUB_SIZE.0, SUB_SIZE.1),
+        }
+    }
+
+    /// Size of the sub, which depends on the shrink power-up
+    fn size(&self, state: &GameState) -> (u32, u32) {
+        if state.power_ups.active(PowerUp::Shrink) {
+            ((SUB_SIZE.0 as f32 * SHRUNK) as u32, (SUB_SIZE.1 as f32 * SHRUNK) as u32)
+        } else {
+            SUB_SIZE
         }
     }
+
+    /// Area of the sub on the screen. A shrunk sub stays centered on the same spot.
+    fn area(&self, state: &GameState) -> Rect {
+        let (w, h) = self.size(state);
+        let x = self.x + self.drift as i32;
+        let y = (self.y + self.angle.sin() * 10.0) as i32;
+
+        Rect::new(x + (SUB_SIZE.0 - w) as i32 / 2, y + (SUB_SIZE.1 - h) as i32 / 2, w, h)
+    }
+
+    /// Glows around the sub for the shield and magnet power-ups
+    fn render_power_ups(&self, state: &GameState, canvas: &mut WindowCanvas, area: Rect) -> Result<(), String> {
+        let mut aura = self.aura.borrow_mut();
+
+        for power_up in [PowerUp::Shield, PowerUp::Magnet] {
+            if !state.power_ups.active(power_up) {
+                continue;
+            }
+
+            let pulse = ((state.fc as f32 / 8.0).sin() + 1.0) / 2.0;
+            let size = (area.width().max(area.height()) as f32 * (1.5 + pulse * 0.2)) as u32;
+            let rect = Rect::from_center(area.center(), size, size);
+
+            let color = power_up.color();
+            aura.set_color_mod(color.0, color.1, color.2);
+            aura.set_alpha_mod(140 + (pulse * 80.0) as u8);
+            canvas.copy(&aura, None, rect)?;
+        }
+
+        Ok(())
+    }
+
+    fn state(&self) -> SubState {
+        SubState {
+            y: self.y,
+            angle: self.angle,
+            velocity: self.velocity,
+            drift: self.drift,
+            drift_velocity: self.drift_velocity,
+            blowing: self.blowing,
+            grace: self.grace,
+            hitbox: self.hitbox,
+            sunk: self.sunk,
+        }
+    }
+
+    fn set_state(&mut self, state: SubState) {
+        self.y = state.y;
+        self.angle = state.angle;
+        self.velocity = state.velocity;
+        self.drift = state.drift;
+        self.drift_velocity = state.drift_velocity;
+        self.blowing = state.blowing;
+        self.grace = state.grace;
+        self.hitbox = state.hitbox;
+        self.sunk = state.sunk;
+    }
+
+    /// Dive! Gives the sub an upward push and a burst of bubbles
+    fn dive(&mut self, physics: &Physics) {
+        self.velocity = physics.jump_strength;
+        self.bubble_burst();
+    }
+
+    fn bubble_burst(&mut self) {
+        self.dive_bubbles.set_spawn_area(Rect::new(self.x + self.drift as i32, self.y as i32 + 10, 20, 30));
+        self.dive_bubbles.burst(8);
+    }
+
 }
 
 impl<'a> Renderable for Sub<'a> {
-    fn render(&self, _state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
-        let y = self.y + (self.angle.sin() * 10.0) as i32;
+    fn render(&self, state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        if !self.playing {
+            return Ok(());
+        }
 
-        canvas.copy_ex(&self.texture, None, Rect::new(self.x, y, 50, 45), self.velocity as f64, None, false, false)?;
+        let theme = state.theme.current();
+        let area = self.area(state);
+
+        self.wake.render(canvas, theme.bubbles)?;
+
+        // Blink while the shield just saved us
+        if (self.grace / 4).is_multiple_of(2) {
+            canvas.copy_ex(&self.texture, self.player.frame_rect(&self.animation), area, self.velocity as f64, None, false, false)?;
+        }
+        self.render_power_ups(state, canvas, area)?;
+
+        self.dive_bubbles.render(canvas, theme.bubbles)?;
+        self.debris.render(canvas, theme.sub)?;
 
         // // Draw bounding box
         // canvas.set_draw_color(Color::RED);
@@ -53,39 +275,83 @@ impl<'a> Renderable for Sub<'a> {
     }
 
     fn update(&mut self, state: &GameState) {
+        self.playing = self.number < state.players;
+        if !self.playing {
+            return;
+        }
+
+        self.grace = self.grace.saturating_sub(1);
+        let sunk = state.game_over || self.sunk;
+
+        // Propeller keeps on spinning and leaves a wake, unless we sunk
+        if !sunk {
+            self.player.update(&self.animation);
+        }
+        // Slow motion churns up a thicker wake
+        let area = self.area(state);
+        self.wake.config.rate = match (sunk, state.power_ups.active(PowerUp::SlowMotion)) {
+            (true, _) => 0.0,
+            (false, true) => 1.5,
+            (false, false) => 0.6,
+        };
+        self.wake.set_spawn_area(Rect::new(area.x() - 2, area.y() + area.height() as i32 * 2 / 5, 4, area.height() / 3));
+
+        let x_speed = state.x_speed as f32;
+        self.wake.update(x_speed);
+        self.dive_bubbles.update(x_speed);
+        self.debris.update(x_speed);
+
         if !state.game_started && ! state.game_over {
             // bobbing in pre-game
             self.angle += 0.04;
             self.angle %= 2.0 * std::f32::consts::PI;
         }
 
-        if state.game_over {
+        if sunk {
             // dead, upside down
             self.angle = 180.0;
         }
 
-        if state.game_started && !state.game_over {
-            self.angle = 0.0;
+        if self.sunk && !state.game_over {
+            // out of the race, while the other players dive on
+            self.y = (self.y + 2.0).min(state.window_height as f32);
+        }
 
-            self.velocity += self.gravity;
-            self.y += self.velocity as i32;
+        if state.game_started && !sunk {
+            self.angle = 0.0;
 
-            if self.velocity < -10.0 {
-                self.velocity = -10.0;
-            }
-            if self.velocity > 10.0 {
-                self.velocity = 10.0;
+            let physics = &state.settings.physics;
+            match physics.control {
+                ControlMode::Dive => self.velocity += physics.gravity,
+                ControlMode::Ballast => {
+                    self.velocity += if self.blowing { -physics.buoyancy } else { physics.gravity };
+                    self.velocity *= physics.drag;
+                }
             }
+            let force = state.forces[self.number];
+            self.velocity += force.1;
+            self.y += self.velocity;
 
-            if self.y > state.window_height as i32 {
-                self.y = state.window_height as i32;
+            // Currents push the sub sideways, while the propeller brings it back to its spot
+            self.drift_velocity = (self.drift_velocity + force.0 - self.drift * DRIFT_RECOVERY) * 0.9;
+            self.drift = (self.drift + self.drift_velocity).clamp(-MAX_DRIFT, MAX_DRIFT);
+
+            self.velocity = self.velocity.clamp(-physics.max_velocity, physics.max_velocity);
+
+            if self.y > state.window_height as f32 {
+                self.y = state.window_height as f32;
                 self.velocity = 0.0;
             }
         }
+
+        self.hitbox = self.area(state);
     }
 
     fn switch_theme(&mut self, theme: &Theme) {
-        self.texture.set_color_mod(theme.sub.0, theme.sub.1, theme.sub.2);
+        let tint = TINTS[self.number];
+        let color = |c: u8, t: u8| (c as u32 * t as u32 / 255) as u8;
+        self.texture.set_color_mod(color(theme.sub.0, tint.0), color(theme.sub.1, tint.1), color(theme.sub.2, tint.2));
+        self.dive_bubbles.set_color_mod(theme.bubbles);
     }
 
     fn reset(&mut self) {
@@ -93,13 +359,117 @@ impl<'a> Renderable for Sub<'a> {
         self.y = self.initial_y;
         self.angle = 0.0;
         self.velocity = 0.0;
+        self.blowing = false;
+        self.drift = 0.0;
+        self.drift_velocity = 0.0;
+        self.grace = 0;
+        self.sunk = false;
+        self.hitbox = Rect::new(self.x, self.y as i32, SUB_SIZE.0, SUB_SIZE.1);
+    }
+
+    fn handle_event(&mut self, event: &Event, state: &GameState) {
+        if !self.playing || self.sunk {
+            return;
+        }
+
+        let (dive, fire) = CONTROLS[self.number];
+        match event {
+            Event::KeyDown { keycode: Some(key), repeat, .. } if *key == dive => match state.settings.physics.control {
+                ControlMode::Dive => {
+                    self.dive(&state.settings.physics);
+                    state.events.emit(GameEvent::Dived);
+                }
+                ControlMode::Ballast if !repeat => {
+                    self.blowing = true;
+                    self.bubble_burst();
+                    state.events.emit(GameEvent::Dived);
+                }
+                ControlMode::Ballast => {}
+            },
+            Event::KeyUp { keycode: Some(key), .. } if *key == dive => self.blowing = false,
+            Event::KeyDown { keycode: Some(key), repeat: false, .. } if *key == fire && state.torpedoes_left() > 0 && !state.game_over => {
+                let area = self.area(state);
+                state.events.emit(GameEvent::TorpedoFired { x: area.right(), y: area.center().y() });
+            }
+            _ => {}
+        }
+    }
+
+    /// Subs that are not in the run, or sunk already, cannot crash or collect anything
+    fn as_collidable(&self) -> Option<&dyn Collidable> {
+        if self.playing && !self.sunk { Some(self) } else { None }
+    }
+
+    fn player(&self) -> Option<usize> {
+        Some(self.number)
+    }
+
+    /// The sub crashed and breaks into pieces, unless the shield absorbs the hit
+    fn on_collision(&mut self, state: &GameState) -> bool {
+        if self.grace > 0 {
+            return false;
+        }
+
+        if state.power_ups.active(PowerUp::Shield) && !state.mode.one_hit() {
+            state.events.emit(GameEvent::ShieldAbsorbed);
+            self.grace = SHIELD_GRACE;
+            return false;
+        }
+
+        self.debris.set_spawn_area(self.area(state));
+        self.debris.burst(25);
+        self.sunk = true;
+        state.events.emit(GameEvent::Sunk { player: self.number });
+        true
+    }
+
+    fn on_event(&mut self, event: &GameEvent, _state: &GameState) {
+        if let GameEvent::Respawned { .. } = event {
+            self.grace = RESPAWN_GRACE;
+            self.sunk = false;
+        }
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.state()))
+    }
+
+    fn restore(&mut self, snapshot: Box<dyn Any>) {
+        if let Ok(state) = snapshot.downcast() {
+            self.set_state(*state);
+        }
+    }
+
+    fn save(&self, w: &mut SaveWriter) {
+        self.state().save(w);
+    }
+
+    fn load(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        self.set_state(Save::load(r)?);
+        Ok(())
     }
 }
 
 impl<'a> Collidable for Sub<'a> {
     fn get_bounding_boxes(&self) -> Vec<Rect> {
-        let y = self.y + (self.angle.sin() * 10.0) as i32;
+        vec![self.hitbox]
+    }
+}
+impl Save for SubState {
+    fn save(&self, w: &mut SaveWriter) {
+        (self.y, self.angle).save(w);
+        (self.velocity, self.blowing).save(w);
+        (self.drift, self.drift_velocity).save(w);
+        (self.grace, self.hitbox).save(w);
+        self.sunk.save(w);
+    }
 
-        vec![Rect::new(self.x, y, 50, 45)]
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let (y, angle) = Save::load(r)?;
+        let (velocity, blowing) = Save::load(r)?;
+        let (drift, drift_velocity) = Save::load(r)?;
+        let (grace, hitbox) = Save::load(r)?;
+        let sunk = Save::load(r)?;
+        Ok(Self { y, angle, velocity, drift, drift_velocity, blowing, grace, hitbox, sunk })
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,166 @@
+use rand::Rng;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use crate::{Collidable, GameState};
+use crate::save::{Save, SaveReader, SaveWriter};
+
+/// Width of a single column of sand
+const COLUMN_WIDTH: i32 = 4;
+
+/// Octaves of noise (wavelength in pixels, amplitude in pixels) that make up the dunes
+const OCTAVES: [(f32, f32); 3] = [(420.0, 30.0), (160.0, 12.0), (45.0, 3.0)];
+
+/// Places the highlights in the sand over the width of the screen. They are just for show, so they are not seeded.
+fn scatter_highlights(w: u32) -> Vec<(i64, i32)> {
+    let mut rng = rand::thread_rng();
+
+    (0..100)
+        .map(|_| (rng.gen_range(0..w as i64), rng.gen_range(4..200)))
+        .collect()
+}
+
+/// Distance between possible rock / shell positions
+const SCATTER_CELL: i64 = 70;
+
+/// Returns a pseudo random value (0.0 - 1.0) for the given integer position
+fn hash(seed: u64, i: i64) -> f32 {
+    let mut x = seed ^ (i as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    x ^= x >> 33;
+    (x >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Smoothly interpolated value noise (0.0 - 1.0)
+fn value_noise(seed: u64, x: f32) -> f32 {
+    let i = x.floor();
+    let t = x - i;
+    let t = t * t * (3.0 - 2.0 * t);
+
+    let a = hash(seed, i as i64);
+    let b = hash(seed, i as i64 + 1);
+    a + (b - a) * t
+}
+
+/// Scrolling sand dunes at the bottom of the ocean
+#[derive(Clone)]
+pub struct Terrain {
+    seed: u64,
+    window_width: u32,
+    window_height: u32,
+    /// How far the terrain has scrolled (world position of the left side of the screen)
+    scroll: i64,
+    /// Small darker pixels in the sand (world x, depth below the surface)
+    highlights: Vec<(i64, i32)>,
+}
+
+impl Terrain {
+    pub fn new(w: u32, h: u32) -> Self {
+        Self {
+            seed: rand::random(),
+            window_width: w,
+            window_height: h,
+            scroll: 0,
+            highlights: scatter_highlights(w),
+        }
+    }
+
+    /// Starts over at the beginning of the dunes of the given seed
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.scroll = 0;
+        self.highlights = scatter_highlights(self.window_width);
+    }
+
+    /// Returns the y position of the surface of the sand at screen position x
+    pub fn surface(&self, x: i32) -> i32 {
+        let world_x = (self.scroll + x as i64) as f32;
+        let base = (self.window_height - self.window_height / 3) as f32;
+
+        let offset: f32 = OCTAVES.iter()
+            .enumerate()
+            .map(|(octave, (wavelength, amplitude))| {
+                (value_noise(self.seed.wrapping_add(octave as u64), world_x / wavelength) - 0.5) * 2.0 * amplitude
+            })
+            .sum();
+
+        (base + offset) as i32
+    }
+
+    /// Writes the shape and position of the dunes to a save file
+    pub fn save(&self, w: &mut SaveWriter) {
+        (self.seed, self.scroll).save(w);
+    }
+
+    pub fn load(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        (self.seed, self.scroll) = Save::load(r)?;
+        Ok(())
+    }
+
+    pub fn update(&mut self, state: &GameState) {
+        self.scroll += state.x_speed as i64;
+
+        // Highlights that scrolled off-screen are placed back on the right side
+        let mut rng = rand::thread_rng();
+        for (x, depth) in &mut self.highlights {
+            if *x < self.scroll {
+                *x = self.scroll + state.window_width as i64 + rng.gen_range(0..20);
+                *depth = rng.gen_range(4..200);
+            }
+        }
+    }
+
+    pub fn render(&self, state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let theme = state.theme.current();
+        let ww = self.window_width as i32;
+
+        // The dunes are a filled polygon, drawn as thin columns from the surface down
+        canvas.set_draw_color(Color::RGB(theme.sand.0, theme.sand.1, theme.sand.2));
+        canvas.fill_rects(&self.get_bounding_boxes())?;
+
+        // Highlights follow the surface
+        canvas.set_draw_color(Color::RGB(theme.sand_highlight.0, theme.sand_highlight.1, theme.sand_highlight.2));
+        for (x, depth) in &self.highlights {
+            let x = (*x - self.scroll) as i32;
+            canvas.fill_rect(Rect::new(x, self.surface(x) + depth, 2, 2))?;
+        }
+
+        // Rocks and shells lying on the surface
+        let first_cell = self.scroll / SCATTER_CELL;
+        for cell in first_cell..=first_cell + (ww as i64 / SCATTER_CELL) + 1 {
+            let roll = hash(self.seed ^ 0x5EED, cell);
+            let x = (cell * SCATTER_CELL - self.scroll) as i32 + (hash(self.seed, cell) * 50.0) as i32;
+            let y = self.surface(x);
+
+            if roll < 0.15 {
+                // Rock, half buried in the sand
+                let size = 8 + (roll * 80.0) as u32;
+                canvas.set_draw_color(Color::RGB(theme.rocks.0, theme.rocks.1, theme.rocks.2));
+                canvas.fill_rect(Rect::new(x, y - size as i32 / 2, size, size))?;
+                canvas.fill_rect(Rect::new(x + 2, y - size as i32 / 2 - 2, size - 4, 2))?;
+            } else if roll < 0.3 {
+                // Shell, a small fan shape
+                canvas.set_draw_color(Color::RGB(theme.fauna_color_4.0, theme.fauna_color_4.1, theme.fauna_color_4.2));
+                canvas.fill_rect(Rect::new(x, y - 3, 7, 3))?;
+                canvas.fill_rect(Rect::new(x + 1, y - 5, 5, 2))?;
+                canvas.fill_rect(Rect::new(x + 2, y - 6, 3, 1))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Collidable for Terrain {
+    /// Bounding boxes following the contour of the dunes
+    fn get_bounding_boxes(&self) -> Vec<Rect> {
+        (0..self.window_width as i32 / COLUMN_WIDTH + 1)
+            .map(|i| {
+                let x = i * COLUMN_WIDTH;
+                let y = self.surface(x);
+                Rect::new(x, y, COLUMN_WIDTH as u32, (self.window_height as i32 - y).max(1) as u32)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,166 @@
+use std::any::Any;
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::video::WindowContext;
+use crate::{Collidable, GameState, Renderable};
+use crate::ecs::{self, Collider, Components, Lifetime, Sprite, Transform, Velocity, World};
+use crate::events::GameEvent;
+use crate::particles::{Emitter, EmitterConfig};
+use crate::save::{Save, SaveReader, SaveWriter};
+use crate::procedural::{in_ellipse, shape_texture};
+use crate::theme::Theme;
+
+/// Size of a torpedo on screen
+const TORPEDO_SIZE: (u32, u32) = (22, 8);
+
+/// Speed of a torpedo, on top of the scrolling
+const TORPEDO_SPEED: f32 = 8.0;
+
+/// Ticks before a torpedo runs out of fuel and sinks out of sight
+const TORPEDO_RANGE: u32 = 120;
+
+/// Color of the explosions
+const EXPLOSION_COLOR: (u8, u8, u8) = (255, 170, 60);
+
+/// Torpedoes fired by the sub. They travel right until they hit an obstacle or run out of fuel.
+pub struct Torpedoes<'a> {
+    textures: Vec<Texture<'a>>,
+    world: World<()>,
+    // Bubbles behind the torpedoes
+    trail: Emitter<'a>,
+    explosions: Emitter<'a>,
+}
+
+impl<'a> Torpedoes<'a> {
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>) -> Self {
+        let texture = shape_texture(texture_creator, TORPEDO_SIZE.0, TORPEDO_SIZE.1, |u, v| {
+            let fin = u < 0.15 && (v - 0.5).abs() < 0.5 - u * 2.0;
+
+            if in_ellipse(u, v, 0.55, 0.5, 0.45, 0.35) || fin {
+                Some((255 - ((v - 0.5).abs() * 200.0) as u8, 255))
+            } else {
+                None
+            }
+        }).unwrap();
+
+        let trail = Emitter::new(EmitterConfig {
+            lifetime: 15..30,
+            velocity_x: -1.0..0.0,
+            velocity_y: -0.5..0.5,
+            drag: 0.95,
+            buoyancy: -0.03,
+            size: (3.0, 1.0),
+            alpha: (180, 0),
+            ..Default::default()
+        });
+
+        let explosions = Emitter::new(EmitterConfig {
+            max_particles: 200,
+            lifetime: 20..45,
+            velocity_x: -4.0..4.0,
+            velocity_y: -4.0..4.0,
+            drag: 0.9,
+            buoyancy: -0.05,
+            size: (7.0, 2.0),
+            alpha: (255, 0),
+            ..Default::default()
+        });
+
+        Self {
+            textures: vec![texture],
+            world: World::new(),
+            trail,
+            explosions,
+        }
+    }
+
+    fn fire(&mut self, x: i32, y: i32) {
+        self.world.spawn(Components {
+            transform: Some(Transform { x: x as f32, y: (y - TORPEDO_SIZE.1 as i32 / 2) as f32, w: TORPEDO_SIZE.0, h: TORPEDO_SIZE.1 }),
+            velocity: Some(Velocity { dx: TORPEDO_SPEED, dy: 0.0, scroll: false }),
+            sprite: Some(Sprite::single(0, None, TORPEDO_SIZE.0, TORPEDO_SIZE.1)),
+            collider: Some(Collider { boxes: vec![Rect::new(0, 0, TORPEDO_SIZE.0, TORPEDO_SIZE.1)] }),
+            lifetime: Some(Lifetime::Ticks(TORPEDO_RANGE)),
+            ..Default::default()
+        });
+    }
+
+    /// Removes the torpedo at the given position, and blows it up
+    fn explode(&mut self, x: i32, y: i32) {
+        for entity in ecs::colliding(&self.world, Rect::new(x, y, 1, 1)) {
+            self.world.despawn(entity);
+        }
+
+        self.explosions.set_spawn_area(Rect::new(x - 8, y - 8, 16, 16));
+        self.explosions.burst(30);
+    }
+}
+
+impl<'a> Renderable for Torpedoes<'a> {
+    fn render(&self, state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+        self.trail.render(canvas, state.theme.current().bubbles)?;
+        ecs::render(&self.world, &self.textures, canvas)?;
+        self.explosions.render(canvas, EXPLOSION_COLOR)
+    }
+
+    fn update(&mut self, state: &GameState) {
+        ecs::movement(&mut self.world, state.x_speed as f32);
+        ecs::culling(&mut self.world);
+
+        for entity in self.world.entities() {
+            if let Some(t) = self.world.transforms[entity] {
+                self.trail.set_spawn_area(Rect::new(t.x as i32, t.y as i32, 2, t.h));
+                self.trail.burst(1);
+            }
+        }
+
+        self.trail.update(state.x_speed as f32);
+        self.explosions.update(state.x_speed as f32);
+    }
+
+    fn switch_theme(&mut self, theme: &Theme) {
+        self.textures[0].set_color_mod(theme.sub.0, theme.sub.1, theme.sub.2);
+    }
+
+    fn reset(&mut self) {
+        self.world.clear();
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.world.clone()))
+    }
+
+    fn restore(&mut self, snapshot: Box<dyn Any>) {
+        if let Ok(world) = snapshot.downcast() {
+            self.world = *world;
+        }
+    }
+
+    fn save(&self, w: &mut SaveWriter) {
+        self.world.save(w);
+    }
+
+    fn load(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        self.world = Save::load(r)?;
+        ecs::check_textures(&self.world, self.textures.len())?;
+        Ok(())
+    }
+
+    fn as_collidable(&self) -> Option<&dyn Collidable> {
+        Some(self)
+    }
+
+    fn on_event(&mut self, event: &GameEvent, _state: &GameState) {
+        match event {
+            GameEvent::TorpedoFired { x, y } => self.fire(*x, *y),
+            GameEvent::ProjectileHit { x, y } => self.explode(*x, *y),
+            _ => {}
+        }
+    }
+}
+
+impl<'a> Collidable for Torpedoes<'a> {
+    fn get_bounding_boxes(&self) -> Vec<Rect> {
+        ecs::bounding_boxes(&self.world)
+    }
+}
@@ -0,0 +1,199 @@
+use std::path::Path;
+use sdl2::image::LoadTexture;
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, TextureCreator};
+use sdl2::video::WindowContext;
+use crate::config::Config;
+use crate::save::{Save, SaveReader, SaveWriter};
+
+/// What happens when an animation reaches its last frame
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Playback {
+    /// Stop at the last frame
+    Once,
+    /// Start over at the first frame
+    Loop,
+    /// Play backwards to the first frame, and then forward again
+    PingPong,
+}
+
+/// Animation as described in the sidecar file (ie: images/sub-large.anim for images/sub-large.png). The frames
+/// are laid out from left to right, top to bottom in the sprite sheet.
+///
+/// ```text
+/// frame_width = 150
+/// frame_height = 130
+/// frames = 4
+/// durations = 4          # ticks per frame, either one value for all frames or one for each frame
+/// playback = loop        # once, loop or pingpong
+/// events = 1:pop         # events fired when a frame is entered (frame:name, frame:name)
+/// ```
+pub struct Animation {
+    frames: Vec<Rect>,
+    durations: Vec<u32>,
+    playback: Playback,
+    events: Vec<(usize, String)>,
+}
+
+impl Animation {
+    /// An animation with a single frame covering the whole texture
+    pub fn single(width: u32, height: u32) -> Self {
+        Self {
+            frames: vec![Rect::new(0, 0, width, height)],
+            durations: vec![1],
+            playback: Playback::Once,
+            events: vec![],
+        }
+    }
+
+    fn from_config(config: &Config, sheet_width: u32, sheet_height: u32) -> Result<Self, String> {
+        let frame_width = config.get_i32("frame_width", sheet_width as i32)?.max(1) as u32;
+        let frame_height = config.get_i32("frame_height", sheet_height as i32)?.max(1) as u32;
+        let columns = (sheet_width / frame_width).max(1);
+        let count = config.get_i32("frames", 1)?.max(1) as u32;
+
+        let frames = (0..count)
+            .map(|i| Rect::new(((i % columns) * frame_width) as i32, ((i / columns) * frame_height) as i32, frame_width, frame_height))
+            .collect();
+
+        let mut durations = vec![];
+        for d in config.get_list("durations") {
+            durations.push(d.parse::<u32>().map_err(|_| format!("invalid duration '{}'", d))?.max(1));
+        }
+        match durations.len() {
+            0 => durations = vec![1; count as usize],
+            1 => durations = vec![durations[0]; count as usize],
+            n if n != count as usize => return Err(format!("expected {} durations, found {}", count, n)),
+            _ => {}
+        }
+
+        let playback = match config.get_str("playback", "loop").as_str() {
+            "once" => Playback::Once,
+            "loop" => Playback::Loop,
+            "pingpong" => Playback::PingPong,
+            s => return Err(format!("unknown playback '{}'", s)),
+        };
+
+        let mut events = vec![];
+        for entry in config.get_list("events") {
+            let Some((frame, name)) = entry.split_once(':') else {
+                return Err(format!("invalid event '{}', expected frame:name", entry));
+            };
+            let frame = frame.trim().parse().map_err(|_| format!("invalid event frame in '{}'", entry))?;
+            events.push((frame, name.trim().to_string()));
+        }
+
+        Ok(Self { frames, durations, playback, events })
+    }
+
+    /// Source rectangle of the first frame, for sprites that are not playing
+    pub fn first_frame(&self) -> Rect {
+        self.frames[0]
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Width and height of a single frame
+    pub fn frame_size(&self) -> (u32, u32) {
+        (self.frames[0].width(), self.frames[0].height())
+    }
+}
+
+/// Playback state of an animation. Many players can share the same animation.
+#[derive(Clone, Default)]
+pub struct AnimationPlayer {
+    frame: usize,
+    ticks: u32,
+    backwards: bool,
+    finished: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Advances the animation by one tick. Returns the name of the event when a frame with an event is entered.
+    pub fn update<'a>(&mut self, animation: &'a Animation) -> Option<&'a str> {
+        if self.finished || animation.frames.len() == 1 {
+            return None;
+        }
+
+        self.ticks += 1;
+        if self.ticks < animation.durations[self.frame] {
+            return None;
+        }
+        self.ticks = 0;
+
+        let last = animation.frames.len() - 1;
+        match animation.playback {
+            Playback::Once if self.frame == last => {
+                self.finished = true;
+                return None;
+            }
+            Playback::Once => self.frame += 1,
+            Playback::Loop => self.frame = (self.frame + 1) % animation.frames.len(),
+            Playback::PingPong => {
+                if (self.backwards && self.frame == 0) || (!self.backwards && self.frame == last) {
+                    self.backwards = !self.backwards;
+                }
+                if self.backwards { self.frame -= 1 } else { self.frame += 1 }
+            }
+        }
+
+        animation.events.iter()
+            .find(|(frame, _)| *frame == self.frame)
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// Returns true when a "once" animation has played its last frame
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Checks that a player read from a save file fits the animation it plays
+    pub fn check(&self, animation: &Animation) -> Result<(), String> {
+        if self.frame < animation.frames.len() {
+            Ok(())
+        } else {
+            Err(format!("invalid animation frame {} in save file", self.frame))
+        }
+    }
+
+    /// Source rectangle of the current frame in the sprite sheet
+    pub fn frame_rect(&self, animation: &Animation) -> Rect {
+        animation.frames[self.frame]
+    }
+}
+
+/// Loads a sprite sheet together with the animation from its sidecar file. Without a sidecar file the whole
+/// texture is a single frame.
+pub fn load_sprite<'a>(texture_creator: &'a TextureCreator<WindowContext>, path: &str) -> Result<(Texture<'a>, Animation), String> {
+    let texture = texture_creator.load_texture(path)?;
+    let q = texture.query();
+
+    let sidecar = Path::new(path).with_extension("anim");
+    let animation = if sidecar.exists() {
+        let config = Config::load(&sidecar)?;
+        Animation::from_config(&config, q.width, q.height).map_err(|e| format!("{}: {}", sidecar.display(), e))?
+    } else {
+        Animation::single(q.width, q.height)
+    };
+
+    Ok((texture, animation))
+}
+
+impl Save for AnimationPlayer {
+    fn save(&self, w: &mut SaveWriter) {
+        (self.frame, self.ticks).save(w);
+        (self.backwards, self.finished).save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let (frame, ticks) = Save::load(r)?;
+        let (backwards, finished) = Save::load(r)?;
+        Ok(Self { frame, ticks, backwards, finished })
+    }
+}
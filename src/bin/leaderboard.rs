@@ -0,0 +1,329 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+/// File with all accepted scores, one "score<tab>mode<tab>name<tab>seed" per line
+const LEADERBOARD_FILE: &str = "flappydive.leaderboard";
+
+/// Clients get this long to send their request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Replays larger than this are refused
+const MAX_BODY: usize = 4 * 1024 * 1024;
+
+/// A verified score
+struct Entry {
+    score: i64,
+    mode: String,
+    name: String,
+    /// Seed of the run, so the same run cannot be sent in twice
+    seed: u64,
+}
+
+impl Entry {
+    fn same_run(&self, other: &Entry) -> bool {
+        (self.score, &self.mode, &self.name, self.seed) == (other.score, &other.mode, &other.name, other.seed)
+    }
+}
+
+/// All verified scores, best scores first
+struct Leaderboard {
+    path: String,
+    entries: Vec<Entry>,
+}
+
+impl Leaderboard {
+    fn load(path: &str) -> Result<Self, String> {
+        let mut leaderboard = Self { path: path.to_string(), entries: vec![] };
+        if !Path::new(path).exists() {
+            return Ok(leaderboard);
+        }
+
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        for (nr, line) in contents.lines().enumerate() {
+            let entry = Self::parse(line).ok_or_else(|| format!("{}:{}: invalid score", path, nr + 1))?;
+            leaderboard.entries.push(entry);
+        }
+        leaderboard.entries.sort_by_key(|e| Reverse(e.score));
+
+        Ok(leaderboard)
+    }
+
+    /// Parses a "score<tab>mode<tab>name<tab>seed" line, as written by the leaderboard and printed by the game when it
+    /// verified a replay
+    fn parse(line: &str) -> Option<Entry> {
+        let mut fields = line.split('\t');
+        let score = fields.next()?.parse().ok()?;
+        let mode = fields.next()?.to_string();
+        let name = fields.next()?.to_string();
+        let seed = fields.next()?.parse().ok()?;
+
+        Some(Entry { score, mode, name, seed })
+    }
+
+    fn contains(&self, entry: &Entry) -> bool {
+        self.entries.iter().any(|e| e.same_run(entry))
+    }
+
+    /// Adds a score and stores the leaderboard. Returns the rank of the score in its mode, and the number of scores
+    /// in that mode.
+    fn add(&mut self, entry: Entry) -> Result<(usize, usize), String> {
+        let idx = self.entries.iter().position(|e| e.score < entry.score).unwrap_or(self.entries.len());
+        self.entries.insert(idx, entry);
+        fs::write(&self.path, self.to_text()).map_err(|e| format!("{}: {}", self.path, e))?;
+
+        let mode = &self.entries[idx].mode;
+        let rank = self.entries[..idx].iter().filter(|e| e.mode == *mode).count() + 1;
+        let total = self.entries.iter().filter(|e| e.mode == *mode).count();
+
+        Ok((rank, total))
+    }
+
+    fn to_text(&self) -> String {
+        self.entries.iter().map(|e| format!("{}\t{}\t{}\t{}\n", e.score, e.mode, e.name, e.seed)).collect()
+    }
+}
+
+/// Leaderboard server for FlappyDive. Players submit the replay of a run, which the game itself plays again
+/// ("flappydive --verify") before the score is accepted. Run it from the directory of the game, so the game finds
+/// its images and biomes.
+///
+/// Usage: leaderboard [--listen <address>] [--game <path to flappydive>]
+fn main() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().collect();
+    let arg = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+
+    let address = arg("--listen").map(|a| a.as_str()).unwrap_or("127.0.0.1:8080");
+    let game = match arg("--game") {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+            exe.with_file_name(format!("flappydive{}", std::env::consts::EXE_SUFFIX))
+        }
+    };
+
+    let mut leaderboard = Leaderboard::load(LEADERBOARD_FILE)?;
+    let listener = TcpListener::bind(address).map_err(|e| format!("cannot listen on {}: {}", address, e))?;
+    println!("Leaderboard listening on http://{}", address);
+
+    // One player at a time: verifying a score takes a moment, but nobody is in a hurry
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+
+        let (status, body) = match read_request(&mut stream) {
+            Ok((method, path, body)) => handle(&method, &path, &body, &game, &mut leaderboard),
+            Err(e) => ("400 Bad Request", e),
+        };
+
+        let response = format!(
+            "HTTP/1.0 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+            status, body.len(), body
+        );
+        // The client is gone, nothing to do about that
+        let _ = stream.write_all(response.as_bytes());
+    }
+
+    Ok(())
+}
+
+/// Reads a request. Returns the method, the path and the body.
+fn read_request(stream: &mut TcpStream) -> Result<(String, String, String), String> {
+    stream.set_read_timeout(Some(READ_TIMEOUT)).map_err(|e| e.to_string())?;
+
+    let mut buf = vec![];
+    let mut chunk = [0u8; 16384];
+    let header_end = loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end;
+        }
+        match stream.read(&mut chunk) {
+            Ok(0) => return Err("request ends too soon".into()),
+            Ok(len) => buf.extend_from_slice(&chunk[..len]),
+            Err(e) => return Err(e.to_string()),
+        }
+        if buf.len() > MAX_BODY {
+            return Err("request is too large".into());
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).into_owned();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or("").split_whitespace();
+    let method = request_line.next().unwrap_or("").to_string();
+    let path = request_line.next().unwrap_or("").to_string();
+
+    let length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .map(|(_, value)| value.trim().parse::<usize>().map_err(|_| "invalid content length".to_string()))
+        .transpose()?
+        .unwrap_or(0);
+    if length > MAX_BODY {
+        return Err("request is too large".into());
+    }
+
+    let mut body = buf.split_off(header_end + 4);
+    while body.len() < length {
+        match stream.read(&mut chunk) {
+            Ok(0) => return Err("request ends too soon".into()),
+            Ok(len) => body.extend_from_slice(&chunk[..len]),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    body.truncate(length);
+
+    let body = String::from_utf8(body).map_err(|_| "body is not text".to_string())?;
+    Ok((method, path, body))
+}
+
+/// Handles a request. Returns the status and the body of the response.
+fn handle(method: &str, path: &str, body: &str, game: &Path, leaderboard: &mut Leaderboard) -> (&'static str, String) {
+    match (method, path) {
+        ("GET", "/scores") => ("200 OK", leaderboard.to_text()),
+        ("POST", "/scores") => {
+            let entry = match verify(body, game) {
+                Ok(entry) => entry,
+                Err(e) => return ("400 Bad Request", format!("Score rejected: {}", e)),
+            };
+            if leaderboard.contains(&entry) {
+                return ("400 Bad Request", "Score rejected: this run is already on the leaderboard".to_string());
+            }
+
+            let (score, mode) = (entry.score, entry.mode.clone());
+            match leaderboard.add(entry) {
+                Ok((rank, total)) => {
+                    println!("Accepted a {} score of {}, number {} of {}", mode, score, rank, total);
+                    ("200 OK", format!("Your score of {} is number {} of {} on the {} leaderboard", score, rank, total, mode))
+                }
+                Err(e) => {
+                    eprintln!("Cannot store the leaderboard: {}", e);
+                    ("500 Internal Server Error", "The leaderboard is out of order".to_string())
+                }
+            }
+        }
+        (_, "/scores") => ("405 Method Not Allowed", String::new()),
+        _ => ("404 Not Found", String::new()),
+    }
+}
+
+/// Lets the game play the replay again. Returns the score when the replay ends in the score it claims.
+fn verify(replay: &str, game: &Path) -> Result<Entry, String> {
+    let path = std::env::temp_dir().join(format!("flappydive-{}.replay", std::process::id()));
+    fs::write(&path, replay).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let output = Command::new(game)
+        .arg("--verify")
+        .arg(&path)
+        .env("SDL_VIDEODRIVER", "dummy")
+        .output()
+        .map_err(|e| format!("cannot run {}: {}", game.display(), e));
+    let _ = fs::remove_file(&path);
+    let output = output?;
+
+    if !output.status.success() {
+        // The game prints the reason as its last words
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().last().unwrap_or("the replay cannot be played");
+        return Err(reason.trim_start_matches("Error: ").trim_matches('"').to_string());
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| Leaderboard::parse(line.strip_prefix("verified\t")?))
+        .ok_or_else(|| "the replay cannot be played".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Shutdown;
+    use std::thread;
+
+    fn entry(score: i64, mode: &str) -> Entry {
+        Entry { score, mode: mode.to_string(), name: "diver".to_string(), seed: 7 }
+    }
+
+    /// Sends raw bytes to a fresh server connection, and reads the request from it
+    fn request(raw: &'static [u8]) -> Result<(String, String, String), String> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            // The server may stop reading and close the connection before everything is sent
+            let _ = stream.write_all(raw);
+            let _ = stream.shutdown(Shutdown::Write);
+        });
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let request = read_request(&mut stream);
+        drop(stream);
+        client.join().unwrap();
+
+        request
+    }
+
+    #[test]
+    fn parse_reads_score_mode_and_name() {
+        let entry = Leaderboard::parse("1200\tClassic\tdiver\t7").unwrap();
+        assert_eq!((entry.score, entry.mode.as_str(), entry.name.as_str(), entry.seed), (1200, "Classic", "diver", 7));
+        let entry = Leaderboard::parse("800\tTime Attack\tsea_diver\t18446744073709551615").unwrap();
+        assert_eq!((entry.score, entry.mode.as_str(), entry.name.as_str(), entry.seed), (800, "Time Attack", "sea_diver", u64::MAX));
+
+        assert!(Leaderboard::parse("1200\tClassic\tdiver").is_none());
+        assert!(Leaderboard::parse("lots\tClassic\tdiver").is_none());
+        assert!(Leaderboard::parse("").is_none());
+    }
+
+    #[test]
+    fn add_ranks_scores_within_their_mode() {
+        let path = std::env::temp_dir().join(format!("flappydive-test-{}.leaderboard", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut leaderboard = Leaderboard { path: path.to_string(), entries: vec![] };
+        assert_eq!(leaderboard.add(entry(100, "Classic")).unwrap(), (1, 1));
+        assert_eq!(leaderboard.add(entry(300, "Classic")).unwrap(), (1, 2));
+        assert_eq!(leaderboard.add(entry(200, "Time Attack")).unwrap(), (1, 1));
+        assert_eq!(leaderboard.add(entry(200, "Classic")).unwrap(), (2, 3));
+        assert_eq!(leaderboard.add(entry(50, "Classic")).unwrap(), (4, 4));
+
+        let reloaded = Leaderboard::load(path).unwrap();
+        let scores: Vec<i64> = reloaded.entries.iter().map(|e| e.score).collect();
+        assert_eq!(scores, [300, 200, 200, 100, 50]);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn the_same_run_is_only_on_the_leaderboard_once() {
+        let leaderboard = Leaderboard { path: String::new(), entries: vec![entry(300, "Classic")] };
+
+        assert!(leaderboard.contains(&entry(300, "Classic")));
+        assert!(!leaderboard.contains(&entry(300, "Time Attack")));
+        assert!(!leaderboard.contains(&entry(200, "Classic")));
+        assert!(!leaderboard.contains(&Entry { seed: 8, ..entry(300, "Classic") }));
+    }
+
+    #[test]
+    fn read_request_returns_method_path_and_body() {
+        let (method, path, body) = request(b"POST /scores HTTP/1.0\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+        assert_eq!((method.as_str(), path.as_str(), body.as_str()), ("POST", "/scores", "hello"));
+    }
+
+    #[test]
+    fn read_request_rejects_truncated_requests() {
+        assert_eq!(request(b"GET /scores HTTP/1.0\r\n").unwrap_err(), "request ends too soon");
+        assert_eq!(request(b"POST /scores HTTP/1.0\r\nContent-Length: 100\r\n\r\nhello").unwrap_err(), "request ends too soon");
+    }
+
+    #[test]
+    fn read_request_rejects_oversized_bodies() {
+        assert_eq!(request(b"POST /scores HTTP/1.0\r\nContent-Length: 99999999\r\n\r\nhello").unwrap_err(), "request is too large");
+        assert_eq!(request(b"POST /scores HTTP/1.0\r\nContent-Length: lots\r\n\r\n").unwrap_err(), "invalid content length");
+    }
+}
@@ -0,0 +1,217 @@
+use std::fs;
+use rand::Rng;
+use crate::actors::current::current_names;
+use crate::actors::enemy::enemy_names;
+use crate::actors::fauna::species_names;
+use crate::actors::pipe::variant_names;
+use crate::config::Config;
+
+type Color = (u8, u8, u8);
+
+/// Directory where the biome definitions can be found
+pub const BIOME_DIR: &str = "biomes";
+
+/// Number of frames it takes to blend from one biome into the next
+pub const BLEND_FRAMES: i64 = 300;
+
+/// Fastest scroll speed a biome can set. Faster, and the sub could skip over a pipe between two frames.
+const MAX_X_SPEED: i32 = 12;
+
+/// Range of the gap scale of a biome. Larger gaps would not fit between the ends of the pipes.
+const MIN_GAP_SCALE: f32 = 0.5;
+const MAX_GAP_SCALE: f32 = 1.5;
+
+/// How the pipes in a biome are decorated
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PipeStyle {
+    Plain,
+    Barnacles,
+}
+
+/// A current placed at a fixed point in a biome
+pub struct CurrentZone {
+    /// Name of the current
+    pub kind: String,
+    /// Frame count at which the current appears at the right of the screen
+    pub at: i64,
+    /// Vertical center of the current
+    pub y: i32,
+}
+
+/// A biome is a section of the ocean the sub travels through
+pub struct Biome {
+    /// Name as displayed in the HUD
+    pub name: String,
+    /// Frame count at which the biome starts
+    pub start: i64,
+    /// Color the current theme is shaded towards (deeper is darker)
+    pub shade: Color,
+    /// Amount of shading (0.0 = no shading, 1.0 = only shade color)
+    pub shade_amount: f32,
+    /// Spawn table: names of the fauna that can be found in this biome, with their relative weights
+    pub fauna: Vec<(String, u32)>,
+    /// Spawn table: names of the enemies that can be found in this biome, with their relative weights
+    pub enemies: Vec<(String, u32)>,
+    /// Chance (0.0 - 1.0) of an enemy appearing with each new pipe
+    pub enemy_chance: f32,
+    /// Spawn table: names of the pipe variants that can be found in this biome, with their relative weights
+    pub pipe_variants: Vec<(String, u32)>,
+    /// Spawn table: names of the currents that can be found in this biome, with their relative weights
+    pub currents: Vec<(String, u32)>,
+    /// Chance (0.0 - 1.0) of a current appearing with each new pipe
+    pub current_chance: f32,
+    /// Currents that always appear at the same spot
+    pub current_zones: Vec<CurrentZone>,
+    /// Pipe decoration
+    pub pipe_style: PipeStyle,
+    /// Scroll speed
+    pub x_speed: i32,
+    /// Multiplier for the size of the holes in the pipes
+    pub gap_scale: f32,
+    /// Parallax speed (fraction of x_speed) of the distant rocks
+    pub rocks_speed: f32,
+    /// Parallax speed (fraction of x_speed) of the kelp
+    pub kelp_speed: f32,
+    /// Chance (0.0 - 1.0) of a kelp stalk growing
+    pub kelp_density: f32,
+    /// Parallax speed (fraction of x_speed) of the debris on the sand
+    pub debris_speed: f32,
+    /// Chance (0.0 - 1.0) of a piece of debris lying on the sand
+    pub debris_density: f32,
+}
+
+impl Biome {
+    fn from_config(config: &Config) -> Result<Self, String> {
+        let pipe_style = match config.get_str("pipe_style", "plain").as_str() {
+            "plain" => PipeStyle::Plain,
+            "barnacles" => PipeStyle::Barnacles,
+            s => return Err(format!("unknown pipe style '{}'", s)),
+        };
+
+        Ok(Self {
+            name: config.get_str("name", "Unknown waters"),
+            start: config.get_i64("start", 0)?,
+            shade: config.get_color("shade", (0, 0, 0))?,
+            shade_amount: config.get_f32("shade_amount", 0.0)?.clamp(0.0, 1.0),
+            fauna: spawn_table(config, "fauna", &species_names())?,
+            enemies: spawn_table(config, "enemies", &enemy_names())?,
+            enemy_chance: config.get_f32("enemy_chance", 0.0)?.clamp(0.0, 1.0),
+            pipe_variants: spawn_table(config, "pipe_variants", &variant_names())?,
+            currents: spawn_table(config, "currents", &current_names())?,
+            current_chance: config.get_f32("current_chance", 0.0)?.clamp(0.0, 1.0),
+            current_zones: current_zones(config)?,
+            pipe_style,
+            x_speed: config.get_i32("x_speed", 3)?.clamp(1, MAX_X_SPEED),
+            gap_scale: config.get_f32("gap_scale", 1.0)?.clamp(MIN_GAP_SCALE, MAX_GAP_SCALE),
+            rocks_speed: config.get_f32("rocks_speed", 0.25)?.clamp(0.0, 1.0),
+            kelp_speed: config.get_f32("kelp_speed", 0.6)?.clamp(0.0, 1.0),
+            kelp_density: config.get_f32("kelp_density", 0.5)?.clamp(0.0, 1.0),
+            debris_speed: config.get_f32("debris_speed", 1.0)?.clamp(0.0, 1.0),
+            debris_density: config.get_f32("debris_density", 0.5)?.clamp(0.0, 1.0),
+        })
+    }
+}
+
+/// Reads a spawn table, written as "name:weight, name:weight". A missing weight means 1. Every name has to be one of
+/// the known names, so a typo does not silently remove a spawn.
+fn spawn_table(config: &Config, key: &str, known: &[&str]) -> Result<Vec<(String, u32)>, String> {
+    let mut table = vec![];
+    for entry in config.get_list(key) {
+        let (name, weight) = entry.split_once(':').unwrap_or((&entry, "1"));
+        let name = name.trim();
+        if !known.contains(&name) {
+            return Err(format!("unknown {} name '{}', expected one of: {}", key, name, known.join(", ")));
+        }
+        let weight = weight.trim().parse().map_err(|_| format!("invalid {} weight in '{}'", key, entry))?;
+        table.push((name.to_string(), weight));
+    }
+
+    Ok(table)
+}
+
+/// Reads the fixed currents, written as "name:frame:y, name:frame:y"
+fn current_zones(config: &Config) -> Result<Vec<CurrentZone>, String> {
+    let mut zones = vec![];
+    for entry in config.get_list("current_zones") {
+        let parts: Vec<_> = entry.split(':').map(|p| p.trim()).collect();
+        let [kind, at, y] = parts[..] else {
+            return Err(format!("invalid current zone '{}'", entry));
+        };
+        if !current_names().contains(&kind) {
+            return Err(format!("unknown current '{}' in current zone '{}'", kind, entry));
+        }
+
+        zones.push(CurrentZone {
+            kind: kind.to_string(),
+            at: at.parse().map_err(|_| format!("invalid current zone frame in '{}'", entry))?,
+            y: y.parse().map_err(|_| format!("invalid current zone position in '{}'", entry))?,
+        });
+    }
+
+    Ok(zones)
+}
+
+/// Picks an item from a table with relative weights
+pub fn pick_weighted<T: Copy>(rng: &mut impl Rng, table: &[(T, u32)]) -> Option<T> {
+    let total: u32 = table.iter().map(|(_, w)| w).sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut roll = rng.gen_range(0..total);
+    for (item, weight) in table {
+        if roll < *weight {
+            return Some(*item);
+        }
+        roll -= weight;
+    }
+
+    None
+}
+
+impl Default for Biome {
+    fn default() -> Self {
+        Self {
+            name: "Open Water".into(),
+            start: 0,
+            shade: (0, 0, 0),
+            shade_amount: 0.0,
+            fauna: vec![("axolotl".into(), 1)],
+            enemies: vec![],
+            enemy_chance: 0.0,
+            pipe_variants: vec![("static".into(), 1)],
+            currents: vec![],
+            current_chance: 0.0,
+            current_zones: vec![],
+            pipe_style: PipeStyle::Plain,
+            x_speed: 3,
+            gap_scale: 1.0,
+            rocks_speed: 0.25,
+            kelp_speed: 0.6,
+            kelp_density: 0.5,
+            debris_speed: 1.0,
+            debris_density: 0.5,
+        }
+    }
+}
+
+/// Loads all biome files (*.biome) from the given directory, sorted on their starting point
+pub fn load_biomes(dir: &str) -> Result<Vec<Biome>, String> {
+    let mut biomes = vec![];
+
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().is_some_and(|ext| ext == "biome") {
+            let config = Config::load(&path)?;
+            biomes.push(Biome::from_config(&config)?);
+        }
+    }
+
+    if biomes.is_empty() {
+        biomes.push(Biome::default());
+    }
+
+    biomes.sort_by_key(|b| b.start);
+    Ok(biomes)
+}
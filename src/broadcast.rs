@@ -0,0 +1,110 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+/// Spectators that have not taken a single byte for this many frames are dropped
+const MAX_STALLED_FRAMES: u32 = 5 * 60;
+
+/// Shares a running game with spectators. Every tick, what is needed to draw the run is sent as a single line of
+/// text. Writing never waits: a spectator that is still busy with an earlier frame skips frames until it caught up.
+pub struct Broadcaster {
+    listener: TcpListener,
+    spectators: Vec<Connection>,
+}
+
+/// A spectator, with the part of the last frame it did not take yet
+struct Connection {
+    stream: TcpStream,
+    pending: Vec<u8>,
+    stalled: u32,
+}
+
+impl Connection {
+    /// Writes as much of the pending frame as the spectator takes right now. Returns false when the spectator is gone
+    /// or has been stuck for too long.
+    fn flush(&mut self) -> bool {
+        let mut progress = false;
+        while !self.pending.is_empty() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => return false,
+                Ok(len) => {
+                    self.pending.drain(..len);
+                    progress = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+
+        self.stalled = if progress || self.pending.is_empty() { 0 } else { self.stalled + 1 };
+        self.stalled < MAX_STALLED_FRAMES
+    }
+}
+
+impl Broadcaster {
+    /// Listens for spectators on the given address ("host:port")
+    pub fn new(address: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(address).map_err(|e| format!("cannot broadcast on {}: {}", address, e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        Ok(Self { listener, spectators: vec![] })
+    }
+
+    /// Sends a frame to all spectators, after letting in the ones that just arrived
+    pub fn send(&mut self, frame: &str) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            let ready = stream.set_nonblocking(true).and_then(|_| stream.set_nodelay(true));
+            if ready.is_ok() {
+                self.spectators.push(Connection { stream, pending: vec![], stalled: 0 });
+            }
+        }
+
+        self.spectators.retain_mut(|s| {
+            // Only start on a new frame once the previous one is out, so every line the spectator gets is whole
+            if s.pending.is_empty() {
+                s.pending.extend_from_slice(frame.as_bytes());
+                s.pending.push(b'\n');
+            }
+            s.flush()
+        });
+    }
+}
+
+/// Watches a game broadcast by another instance
+pub struct Spectator {
+    stream: TcpStream,
+    buf: Vec<u8>,
+}
+
+impl Spectator {
+    pub fn connect(address: &str) -> Result<Self, String> {
+        let stream = TcpStream::connect(address).map_err(|e| format!("cannot spectate {}: {}", address, e))?;
+        stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        Ok(Self { stream, buf: vec![] })
+    }
+
+    /// Returns the most recent frame that arrived since the last call, if any. Older frames are skipped, so a slow
+    /// spectator catches up instead of lagging behind. Returns an error when the broadcast ended.
+    pub fn latest(&mut self) -> Result<Option<String>, String> {
+        let mut chunk = [0u8; 16384];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err("the broadcast ended".into()),
+                Ok(len) => self.buf.extend_from_slice(&chunk[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+
+        let Some(end) = self.buf.iter().rposition(|b| *b == b'\n') else {
+            return Ok(None);
+        };
+        let start = self.buf[..end].iter().rposition(|b| *b == b'\n').map(|i| i + 1).unwrap_or(0);
+
+        let frame = String::from_utf8_lossy(&self.buf[start..end]).into_owned();
+        self.buf.drain(..=end);
+
+        Ok(Some(frame))
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Simple "key = value" configuration file. Lines starting with '#' are comments.
+pub struct Config {
+    path: String,
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref().display().to_string();
+        let contents = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+
+        Self::parse(&path, &contents)
+    }
+
+    pub fn parse(path: &str, contents: &str) -> Result<Self, String> {
+        let mut values = HashMap::new();
+
+        for (nr, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("{}:{}: expected 'key = value'", path, nr + 1));
+            };
+
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+
+        Ok(Self { path: path.to_string(), values })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.as_str())
+    }
+
+    /// All keys in the file, in no particular order
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(|k| k.as_str())
+    }
+
+    pub fn get_str(&self, key: &str, default: &str) -> String {
+        self.get(key).unwrap_or(default).to_string()
+    }
+
+    pub fn get_i32(&self, key: &str, default: i32) -> Result<i32, String> {
+        match self.get(key) {
+            Some(v) => v.parse().map_err(|_| self.error(key, "an integer")),
+            None => Ok(default),
+        }
+    }
+
+    pub fn get_i64(&self, key: &str, default: i64) -> Result<i64, String> {
+        match self.get(key) {
+            Some(v) => v.parse().map_err(|_| self.error(key, "an integer")),
+            None => Ok(default),
+        }
+    }
+
+    pub fn get_f32(&self, key: &str, default: f32) -> Result<f32, String> {
+        match self.get(key) {
+            Some(v) => v.parse().map_err(|_| self.error(key, "a number")),
+            None => Ok(default),
+        }
+    }
+
+    pub fn get_bool(&self, key: &str, default: bool) -> Result<bool, String> {
+        match self.get(key) {
+            Some("true") | Some("yes") | Some("1") => Ok(true),
+            Some("false") | Some("no") | Some("0") => Ok(false),
+            Some(_) => Err(self.error(key, "true or false")),
+            None => Ok(default),
+        }
+    }
+
+    /// Colors are written as "r, g, b"
+    pub fn get_color(&self, key: &str, default: (u8, u8, u8)) -> Result<(u8, u8, u8), String> {
+        let Some(v) = self.get(key) else {
+            return Ok(default);
+        };
+
+        let parts: Vec<u8> = v.split(',').filter_map(|p| p.trim().parse().ok()).collect();
+        match parts[..] {
+            [r, g, b] => Ok((r, g, b)),
+            _ => Err(self.error(key, "a color (r, g, b)")),
+        }
+    }
+
+    /// Lists are written as comma separated values
+    pub fn get_list(&self, key: &str) -> Vec<String> {
+        match self.get(key) {
+            Some(v) => v.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect(),
+            None => vec![],
+        }
+    }
+
+    fn error(&self, key: &str, expected: &str) -> String {
+        format!("{}: '{}' should be {}", self.path, key, expected)
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::config::Config;
+
+/// File with the results of the daily challenges played on this machine
+pub const DAILY_FILE: &str = "flappydive.daily";
+
+/// Days since 1970-01-01 (UTC)
+pub fn today() -> i64 {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    (secs / 86_400) as i64
+}
+
+/// Formats a day as "yyyy-mm-dd"
+pub fn date(day: i64) -> String {
+    // Converts days to a civil date, with years starting in March so the leap day is at the end
+    let z = day + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// Seed of the daily challenge of the given day. Everyone playing on the same day dives through the same ocean.
+pub fn seed(day: i64) -> u64 {
+    let mut z = (day as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ 0xf1a9_9d17_e000_0000;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Scores of earlier daily challenges, by date. Only the first attempt of every day is kept.
+pub struct DailyResults {
+    path: String,
+    scores: BTreeMap<String, i64>,
+}
+
+impl DailyResults {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut scores = BTreeMap::new();
+
+        if Path::new(path).exists() {
+            let config = Config::load(path)?;
+            for date in config.keys() {
+                scores.insert(date.to_string(), config.get_i64(date, 0)?);
+            }
+        }
+
+        Ok(Self { path: path.to_string(), scores })
+    }
+
+    /// Score of the daily challenge of the given day, if it was played
+    pub fn score(&self, day: i64) -> Option<i64> {
+        self.scores.get(&date(day)).copied()
+    }
+
+    /// Number of daily challenges played
+    pub fn played(&self) -> usize {
+        self.scores.len()
+    }
+
+    /// Date and score of the best daily challenge
+    pub fn best(&self) -> Option<(&str, i64)> {
+        self.scores.iter()
+            .max_by_key(|(_, score)| **score)
+            .map(|(date, score)| (date.as_str(), *score))
+    }
+
+    /// Stores the score of the daily challenge of the given day, unless there already is one
+    pub fn record(&mut self, day: i64, score: i64) -> Result<(), String> {
+        if self.score(day).is_some() {
+            return Ok(());
+        }
+        self.scores.insert(date(day), score);
+
+        let mut contents = "# FlappyDive daily challenge results\n".to_string();
+        for (date, score) in &self.scores {
+            contents += &format!("{} = {}\n", date, score);
+        }
+
+        fs::write(&self.path, contents).map_err(|e| format!("{}: {}", self.path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_formats_days_since_the_epoch() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(19_723), "2024-01-01");
+        assert_eq!(date(19_782), "2024-02-29");
+        assert_eq!(date(19_783), "2024-03-01");
+        assert_eq!(date(-1), "1969-12-31");
+    }
+
+    #[test]
+    fn seed_is_the_same_for_everyone_on_the_same_day() {
+        assert_eq!(seed(19_723), seed(19_723));
+        assert_ne!(seed(19_723), seed(19_724));
+        assert_ne!(seed(0), 0);
+    }
+
+    #[test]
+    fn only_the_first_result_of_a_day_is_kept() {
+        let path = std::env::temp_dir().join(format!("flappydive-test-{}.daily", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut results = DailyResults::load(path).unwrap();
+        results.record(19_723, 100).unwrap();
+        results.record(19_723, 500).unwrap();
+        results.record(19_724, 300).unwrap();
+        assert_eq!(results.score(19_723), Some(100));
+
+        let results = DailyResults::load(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(results.played(), 2);
+        assert_eq!(results.best(), Some(("2024-01-02", 300)));
+    }
+}
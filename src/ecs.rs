@@ -0,0 +1,403 @@
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, WindowCanvas};
+use crate::save::{Save, SaveReader, SaveWriter};
+
+/// An entity is nothing more than an index into the component lists of a world
+pub type Entity = usize;
+
+/// Position and size of an entity
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    pub x: f32,
+    pub y: f32,
+    pub w: u32,
+    pub h: u32,
+}
+
+/// Movement of an entity per tick
+#[derive(Clone, Copy, Debug)]
+pub struct Velocity {
+    pub dx: f32,
+    pub dy: f32,
+    /// When true, the entity also scrolls along with the world (x_speed)
+    pub scroll: bool,
+}
+
+/// Part of a sprite, relative to the transform of the entity
+#[derive(Clone, Copy, Debug)]
+pub struct SpritePart {
+    /// Index in the texture list given to the render system
+    pub texture: usize,
+    /// Source rectangle in the texture, or None for the whole texture
+    pub src: Option<Rect>,
+    /// Destination, relative to the transform
+    pub dst: Rect,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+/// What an entity looks like. Most entities have a single part, but a pipe for instance has four.
+#[derive(Clone, Debug)]
+pub struct Sprite {
+    pub parts: Vec<SpritePart>,
+}
+
+impl Sprite {
+    /// A sprite with a single part, covering the whole transform
+    pub fn single(texture: usize, src: Option<Rect>, w: u32, h: u32) -> Self {
+        Self {
+            parts: vec![SpritePart { texture, src, dst: Rect::new(0, 0, w, h), flip_horizontal: false, flip_vertical: false }],
+        }
+    }
+}
+
+/// Bounding boxes of an entity, relative to the transform
+#[derive(Clone, Debug)]
+pub struct Collider {
+    pub boxes: Vec<Rect>,
+}
+
+/// When an entity is removed
+#[derive(Clone, Copy, Debug)]
+pub enum Lifetime {
+    /// When it has moved off the left or top of the screen
+    OffScreen,
+    /// After a number of ticks (or when it moved off-screen)
+    Ticks(u32),
+}
+
+/// Points an entity is worth once the player has passed it
+#[derive(Clone, Copy, Debug)]
+pub struct Scoring {
+    pub points: i64,
+    pub passed: bool,
+}
+
+/// Components for spawning a new entity. T is the component type that is specific for the kind of entities in the
+/// world (ie: pipe data or creature behaviour).
+pub struct Components<T> {
+    pub transform: Option<Transform>,
+    pub velocity: Option<Velocity>,
+    pub sprite: Option<Sprite>,
+    pub collider: Option<Collider>,
+    pub lifetime: Option<Lifetime>,
+    pub scoring: Option<Scoring>,
+    pub data: Option<T>,
+}
+
+impl<T> Default for Components<T> {
+    fn default() -> Self {
+        Self {
+            transform: None,
+            velocity: None,
+            sprite: None,
+            collider: None,
+            lifetime: None,
+            scoring: None,
+            data: None,
+        }
+    }
+}
+
+/// Holds all the entities and their components. Components are stored in lists indexed by entity.
+#[derive(Clone)]
+pub struct World<T> {
+    alive: Vec<bool>,
+    pub transforms: Vec<Option<Transform>>,
+    pub velocities: Vec<Option<Velocity>>,
+    pub sprites: Vec<Option<Sprite>>,
+    pub colliders: Vec<Option<Collider>>,
+    pub lifetimes: Vec<Option<Lifetime>>,
+    pub scorings: Vec<Option<Scoring>>,
+    pub data: Vec<Option<T>>,
+}
+
+impl<T> World<T> {
+    pub fn new() -> Self {
+        Self {
+            alive: Vec::new(),
+            transforms: Vec::new(),
+            velocities: Vec::new(),
+            sprites: Vec::new(),
+            colliders: Vec::new(),
+            lifetimes: Vec::new(),
+            scorings: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Adds a new entity, reusing the slot of a removed entity when possible
+    pub fn spawn(&mut self, c: Components<T>) -> Entity {
+        let entity = match self.alive.iter().position(|alive| !alive) {
+            Some(entity) => entity,
+            None => {
+                self.alive.push(false);
+                self.transforms.push(None);
+                self.velocities.push(None);
+                self.sprites.push(None);
+                self.colliders.push(None);
+                self.lifetimes.push(None);
+                self.scorings.push(None);
+                self.data.push(None);
+                self.alive.len() - 1
+            }
+        };
+
+        self.alive[entity] = true;
+        self.transforms[entity] = c.transform;
+        self.velocities[entity] = c.velocity;
+        self.sprites[entity] = c.sprite;
+        self.colliders[entity] = c.collider;
+        self.lifetimes[entity] = c.lifetime;
+        self.scorings[entity] = c.scoring;
+        self.data[entity] = c.data;
+
+        entity
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.alive[entity] = false;
+        self.transforms[entity] = None;
+        self.velocities[entity] = None;
+        self.sprites[entity] = None;
+        self.colliders[entity] = None;
+        self.lifetimes[entity] = None;
+        self.scorings[entity] = None;
+        self.data[entity] = None;
+    }
+
+    pub fn clear(&mut self) {
+        for entity in self.entities().collect::<Vec<_>>() {
+            self.despawn(entity);
+        }
+    }
+
+    /// All living entities
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.alive.iter().enumerate().filter(|(_, alive)| **alive).map(|(e, _)| e)
+    }
+}
+
+/// Movement system: applies velocity (and world scrolling) to the transforms
+pub fn movement<T>(world: &mut World<T>, x_speed: f32) {
+    for (transform, velocity) in world.transforms.iter_mut().zip(world.velocities.iter()) {
+        if let (Some(t), Some(v)) = (transform, velocity) {
+            if v.scroll {
+                t.x -= x_speed;
+            }
+            t.x += v.dx;
+            t.y += v.dy;
+        }
+    }
+}
+
+/// Culling system: removes entities whose lifetime is over
+pub fn culling<T>(world: &mut World<T>) {
+    let mut finished = vec![];
+
+    for entity in world.entities().collect::<Vec<_>>() {
+        let (Some(lifetime), Some(t)) = (&mut world.lifetimes[entity], &world.transforms[entity]) else {
+            continue;
+        };
+
+        let off_screen = t.x + (t.w as f32) < 0.0 || t.y + (t.h as f32) < 0.0;
+        let expired = match lifetime {
+            Lifetime::OffScreen => false,
+            Lifetime::Ticks(ticks) => {
+                *ticks = ticks.saturating_sub(1);
+                *ticks == 0
+            }
+        };
+
+        if off_screen || expired {
+            finished.push(entity);
+        }
+    }
+
+    for entity in finished {
+        world.despawn(entity);
+    }
+}
+
+/// Collision system: returns the bounding boxes of all colliders in screen coordinates
+pub fn bounding_boxes<T>(world: &World<T>) -> Vec<Rect> {
+    world.entities()
+        .filter_map(|e| Some((world.transforms[e]?, world.colliders[e].as_ref()?)))
+        .flat_map(|(t, c)| c.boxes.iter().map(move |b| Rect::new(t.x as i32 + b.x(), t.y as i32 + b.y(), b.width(), b.height())))
+        .collect()
+}
+
+/// Collision system: returns the entities that have a collider intersecting with the given box
+pub fn colliding<T>(world: &World<T>, bb: Rect) -> Vec<Entity> {
+    world.entities()
+        .filter(|e| {
+            let (Some(t), Some(c)) = (world.transforms[*e], world.colliders[*e].as_ref()) else {
+                return false;
+            };
+            c.boxes.iter().any(|b| bb.has_intersection(Rect::new(t.x as i32 + b.x(), t.y as i32 + b.y(), b.width(), b.height())))
+        })
+        .collect()
+}
+
+/// Scoring system: returns the points of all entities that moved past the given x position since the last call
+pub fn scoring<T>(world: &mut World<T>, player_x: i32) -> i64 {
+    let mut points = 0;
+
+    for (transform, scoring) in world.transforms.iter().zip(world.scorings.iter_mut()) {
+        if let (Some(t), Some(s)) = (transform, scoring) {
+            if !s.passed && (t.x as i32 + t.w as i32) < player_x {
+                s.passed = true;
+                points += s.points;
+            }
+        }
+    }
+
+    points
+}
+
+/// Checks that all sprites of a world read from a save file use one of the given number of textures
+pub fn check_textures<T>(world: &World<T>, textures: usize) -> Result<(), String> {
+    let parts = world.sprites.iter().flatten().flat_map(|s| s.parts.iter());
+    match parts.map(|p| p.texture).find(|t| *t >= textures) {
+        Some(texture) => Err(format!("invalid texture {} in save file", texture)),
+        None => Ok(()),
+    }
+}
+
+/// Render system: draws all sprites with the given textures
+pub fn render<T>(world: &World<T>, textures: &[Texture], canvas: &mut WindowCanvas) -> Result<(), String> {
+    for entity in world.entities() {
+        let (Some(t), Some(sprite)) = (world.transforms[entity], world.sprites[entity].as_ref()) else {
+            continue;
+        };
+
+        for part in &sprite.parts {
+            let dst = Rect::new(t.x as i32 + part.dst.x(), t.y as i32 + part.dst.y(), part.dst.width(), part.dst.height());
+            canvas.copy_ex(&textures[part.texture], part.src, dst, 0.0, None, part.flip_horizontal, part.flip_vertical)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl Save for Transform {
+    fn save(&self, w: &mut SaveWriter) {
+        (self.x, self.y).save(w);
+        (self.w, self.h).save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let (x, y) = Save::load(r)?;
+        let (w, h) = Save::load(r)?;
+        Ok(Self { x, y, w, h })
+    }
+}
+
+impl Save for Velocity {
+    fn save(&self, w: &mut SaveWriter) {
+        (self.dx, self.dy).save(w);
+        self.scroll.save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let (dx, dy) = Save::load(r)?;
+        Ok(Self { dx, dy, scroll: Save::load(r)? })
+    }
+}
+
+impl Save for SpritePart {
+    fn save(&self, w: &mut SaveWriter) {
+        (self.texture, self.src).save(w);
+        self.dst.save(w);
+        (self.flip_horizontal, self.flip_vertical).save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let (texture, src) = Save::load(r)?;
+        let dst = Save::load(r)?;
+        let (flip_horizontal, flip_vertical) = Save::load(r)?;
+        Ok(Self { texture, src, dst, flip_horizontal, flip_vertical })
+    }
+}
+
+impl Save for Sprite {
+    fn save(&self, w: &mut SaveWriter) {
+        self.parts.save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        Ok(Self { parts: Save::load(r)? })
+    }
+}
+
+impl Save for Collider {
+    fn save(&self, w: &mut SaveWriter) {
+        self.boxes.save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        Ok(Self { boxes: Save::load(r)? })
+    }
+}
+
+impl Save for Lifetime {
+    fn save(&self, w: &mut SaveWriter) {
+        // Off-screen is written as no ticks
+        match self {
+            Lifetime::OffScreen => None::<u32>.save(w),
+            Lifetime::Ticks(ticks) => Some(*ticks).save(w),
+        }
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        Ok(match Option::<u32>::load(r)? {
+            None => Lifetime::OffScreen,
+            Some(ticks) => Lifetime::Ticks(ticks),
+        })
+    }
+}
+
+impl Save for Scoring {
+    fn save(&self, w: &mut SaveWriter) {
+        (self.points, self.passed).save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let (points, passed) = Save::load(r)?;
+        Ok(Self { points, passed })
+    }
+}
+
+impl<T: Save> Save for World<T> {
+    fn save(&self, w: &mut SaveWriter) {
+        self.alive.save(w);
+        self.transforms.save(w);
+        self.velocities.save(w);
+        self.sprites.save(w);
+        self.colliders.save(w);
+        self.lifetimes.save(w);
+        self.scorings.save(w);
+        self.data.save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let world = Self {
+            alive: Save::load(r)?,
+            transforms: Save::load(r)?,
+            velocities: Save::load(r)?,
+            sprites: Save::load(r)?,
+            colliders: Save::load(r)?,
+            lifetimes: Save::load(r)?,
+            scorings: Save::load(r)?,
+            data: Save::load(r)?,
+        };
+
+        let len = world.alive.len();
+        if [world.transforms.len(), world.velocities.len(), world.sprites.len(), world.colliders.len(),
+            world.lifetimes.len(), world.scorings.len(), world.data.len()].iter().any(|l| *l != len) {
+            return Err("entities in save file do not match up".into());
+        }
+
+        Ok(world)
+    }
+}
@@ -0,0 +1,85 @@
+use std::cell::RefCell;
+use crate::powerup::PowerUp;
+
+/// When this environment variable is set, all game events are logged to stderr
+pub const LOG_EVENTS_ENV: &str = "FLAPPYDIVE_LOG_EVENTS";
+
+/// Things that happen during the game that other parts of the game can react to
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    /// A new run has started
+    RunStarted,
+    /// The run has ended with the given score
+    RunEnded { score: i64 },
+    /// The player dived
+    Dived,
+    /// A new pipe appeared at x, with a gap between gap_top and gap_bottom
+    PipeSpawned { x: i32, gap_top: i32, gap_bottom: i32 },
+    /// The gap of the pipe at x moved, and is now between gap_top and gap_bottom
+    PipeMoved { x: i32, gap_top: i32, gap_bottom: i32 },
+    /// The player passed a pipe
+    PipePassed,
+    /// The player came very close to an obstacle, but did not hit it
+    NearMiss,
+    /// The player crashed into an obstacle
+    Collided,
+    /// The sub of the given player crashed, and is out of the run
+    Sunk { player: usize },
+    /// The shield of the player absorbed a collision
+    ShieldAbsorbed,
+    /// The player picked up a power-up
+    PickedUp { power_up: PowerUp },
+    /// The player caught an air pocket
+    AirCollected,
+    /// The player picked up torpedoes
+    AmmoCollected { count: u32 },
+    /// The player fired a torpedo from the given position
+    TorpedoFired { x: i32, y: i32 },
+    /// A projectile hit something at the given position
+    ProjectileHit { x: i32, y: i32 },
+    /// The player destroyed something worth bonus points
+    Destroyed { points: i64 },
+    /// The player ran out of oxygen
+    OutOfAir,
+    /// The player crashed or ran out of oxygen during practice, and loses a life
+    LifeLost,
+    /// The player continues from the given checkpoint during practice
+    Respawned { checkpoint: u32 },
+    /// The colors of the game changed, either by the player or by entering a new biome
+    ThemeChanged { biome: String },
+}
+
+/// Queue of events. Anything with access to the game state can emit events, which are delivered to all actors at the
+/// end of the frame. Emitters do not need to know who is listening.
+pub struct EventBus {
+    queue: RefCell<Vec<GameEvent>>,
+    log: bool,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self {
+            queue: RefCell::new(Vec::new()),
+            log: std::env::var_os(LOG_EVENTS_ENV).is_some(),
+        }
+    }
+
+    pub fn emit(&self, event: GameEvent) {
+        self.queue.borrow_mut().push(event);
+    }
+
+    /// Takes all queued events
+    pub fn drain(&self) -> Vec<GameEvent> {
+        self.queue.take()
+    }
+
+    /// Number of queued events for which the predicate holds
+    pub fn queued(&self, predicate: impl Fn(&GameEvent) -> bool) -> usize {
+        self.queue.borrow().iter().filter(|e| predicate(e)).count()
+    }
+
+    /// Returns true when events should be logged
+    pub fn logging(&self) -> bool {
+        self.log
+    }
+}
@@ -0,0 +1,49 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Time to wait for the server. Verifying a score replays the whole run, so the answer can take a while.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fetches a path from a server ("http://host:port"). Returns the body of the response.
+pub fn get(url: &str, path: &str) -> Result<String, String> {
+    request(url, "GET", path, "")
+}
+
+/// Sends a body to a path on a server ("http://host:port"). Returns the body of the response.
+pub fn post(url: &str, path: &str, body: &str) -> Result<String, String> {
+    request(url, "POST", path, body)
+}
+
+/// Just enough HTTP/1.0 to talk to the leaderboard server: plain text bodies, and the connection closes after every
+/// request, so the response ends where the stream ends.
+fn request(url: &str, method: &str, path: &str, body: &str) -> Result<String, String> {
+    let host = url.strip_prefix("http://").ok_or_else(|| format!("{}: only http:// is supported", url))?;
+    let host = host.trim_end_matches('/');
+
+    let addr = host.to_socket_addrs()
+        .map_err(|e| format!("{}: {}", host, e))?
+        .next()
+        .ok_or_else(|| format!("{}: unknown host", host))?;
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT).map_err(|e| format!("{}: {}", host, e))?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+
+    let request = format!(
+        "{} {} HTTP/1.0\r\nHost: {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+        method, path, host, body.len(), body
+    );
+    stream.write_all(request.as_bytes()).map_err(|e| format!("{}: {}", host, e))?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).map_err(|e| format!("{}: {}", host, e))?;
+
+    let (head, body) = response.split_once("\r\n\r\n").ok_or_else(|| format!("{}: invalid response", host))?;
+    let status = head.split_whitespace().nth(1).unwrap_or("");
+    match body.trim() {
+        _ if status == "200" => {}
+        "" => return Err(format!("{}: server answered {}", host, status)),
+        reason => return Err(reason.to_string()),
+    }
+
+    Ok(body.to_string())
+}
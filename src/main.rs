@@ -1,27 +1,90 @@
 mod theme;
 mod actors;
+mod animation;
+mod biome;
+mod broadcast;
+mod config;
+mod daily;
+mod ecs;
+mod events;
+mod http;
+mod mode;
+mod net;
+mod oxygen;
+mod particles;
+mod physics;
+mod powerup;
+mod procedural;
+mod replay;
+mod rewind;
+mod save;
+mod rng;
+mod settings;
 
 extern crate sdl2;
 
+use std::any::Any;
+use std::cell::{RefCell, RefMut};
+use std::fs;
+use std::path::Path;
 use std::process::exit;
 use std::time::Duration;
 use sdl2::pixels::Color;
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Scancode};
 use sdl2::rect::Rect;
-use sdl2::render::WindowCanvas;
+use sdl2::render::{BlendMode, WindowCanvas};
 use sdl2::ttf::Sdl2TtfContext;
-use crate::actors::Actors;
+use crate::actors::{layer, Actors, CollisionTag, Scene};
 use crate::actors::background::Background;
 use crate::actors::bubble::Bubbles;
+use crate::actors::current::Currents;
+use crate::actors::enemy::Enemies;
+use crate::actors::fauna::Fauna;
+use crate::actors::ghost::Ghosts;
+use crate::actors::pickup::Pickups;
 use crate::actors::pipe::Pipes;
 use crate::actors::score::Score;
 use crate::actors::sub::Sub;
-use crate::theme::{Theme, ThemeSwitcher};
+use crate::actors::torpedo::Torpedoes;
+use crate::biome::{load_biomes, BIOME_DIR};
+use crate::broadcast::{Broadcaster, Spectator};
+use crate::daily::{DailyResults, DAILY_FILE};
+use crate::events::{EventBus, GameEvent};
+use crate::mode::{GameMode, ALL_MODES};
+use crate::net::NetRace;
+use crate::oxygen::Oxygen;
+use crate::powerup::{PowerUp, PowerUps};
+use crate::replay::Replay;
+use crate::rewind::{Rewind, RewindMode, Snapshot, REWIND_PROMPT};
+use crate::rng::GameRng;
+use crate::save::{load_variant, save_variant, Save, SaveReader, SaveWriter, SAVE_FILE};
+use crate::settings::{Settings, SETTINGS_FILE};
+use crate::theme::{Theme, ThemeChange, ThemeSwitcher};
 
 // Number of bubbles on the screen at one time
 pub const MAX_BUBBLES: usize = 15;
 
+// Horizontal position of the sub on the screen
+pub const PLAYER_X: i32 = 100;
+
+// Number of subs that can dive at the same time, in versus
+pub const MAX_PLAYERS: usize = 2;
+
+// Torpedoes at the start of a run, and the most the sub can carry
+const START_TORPEDOES: u32 = 3;
+const MAX_TORPEDOES: u32 = 9;
+
+// Lives in practice mode, and the number of pipes between checkpoints
+const START_LIVES: u32 = 3;
+const CHECKPOINT_PIPES: u32 = 10;
+
+// Number of scores shown on the online leaderboard
+const LEADERBOARD_LINES: usize = 10;
+
+// A replay that has no input for this many frames (five minutes) is not going anywhere
+const MAX_IDLE_FRAMES: i64 = 5 * 60 * 60;
+
 
 /// Game state
 struct GameState {
@@ -31,8 +94,10 @@ struct GameState {
     game_over: bool,
     /// Current frame counter
     fc: i64,
-    /// Highest frame counter score encountered
-    high_score: i64,
+    /// Highest score encountered, for every mode
+    high_scores: [i64; ALL_MODES.len()],
+    /// Highest score of practice runs, which do not count for the real high score
+    practice_high_score: i64,
     /// Speed of the current game
     x_speed: i32,
     // Current window height
@@ -42,7 +107,151 @@ struct GameState {
     // Theme switcher
     theme: ThemeSwitcher,
     // Runcount
-    run_count: i32
+    run_count: i32,
+    // User settings
+    settings: Settings,
+    // Events emitted during the current frame
+    events: EventBus,
+    // Active power-ups
+    power_ups: PowerUps,
+    // Oxygen left in the sub
+    oxygen: Oxygen,
+    // Area covered by the player, if any, and by every sub still in the run on its own
+    player: Option<Rect>,
+    player_areas: Vec<(usize, Rect)>,
+    // Torpedoes left
+    torpedoes: u32,
+    // Bonus points for destroying things in the current run
+    bonus: i64,
+    // Force (x, y) pushing every player around, like the jets of vents
+    forces: [(f32, f32); MAX_PLAYERS],
+    // Random number generator that shapes the run, and the seed it started with
+    rng: RefCell<GameRng>,
+    seed: u64,
+    // Pipes passed in the current run
+    pipes: u32,
+    // Practice mode: crashing costs a life, and the run continues from the last checkpoint
+    practice: bool,
+    lives: u32,
+    // Checkpoint the next practice run starts at, and the furthest checkpoint reached with the current seed
+    start_checkpoint: u32,
+    reached_checkpoint: u32,
+    // Recent history of the run, so the player can rewind after a crash
+    rewind: Rewind,
+    // Rules of the run
+    mode: GameMode,
+    // Distance travelled in the current run, in pixels
+    distance: i64,
+    // Day of the daily challenge when playing it, and the results of earlier daily challenges
+    daily: Option<i64>,
+    daily_results: DailyResults,
+    // Number of subs in the run: one, or more in versus
+    players: usize,
+    // Frame at which every player sank in the current run, and the player that won the last versus run
+    sunk_at: [Option<i64>; MAX_PLAYERS],
+    winner: Option<usize>,
+    // Network race, when racing other players
+    net: Option<NetRace>,
+    // Race our sub sank in, which stays open until the other racers are done, and the standings of the last race
+    finished_race: Option<NetRace>,
+    standings: Option<String>,
+    // Spectators watching the game, when broadcasting
+    broadcast: Option<Broadcaster>,
+    // Replay of the current or last run, when its score can be verified by the leaderboard server
+    replay: Option<Replay>,
+    // Answer of the leaderboard server to the score of the last run
+    submitted: Option<String>,
+}
+
+impl GameState {
+    /// Score of the current run
+    fn score(&self) -> i64 {
+        self.mode.score(self.fc, self.distance, self.bonus)
+    }
+
+    /// High score to beat in the current run
+    fn high_score(&self) -> i64 {
+        if self.practice { self.practice_high_score } else { self.high_scores[self.mode.index()] }
+    }
+
+    /// Torpedoes that can still be fired. Torpedoes fired in this frame only leave the stock when the events are
+    /// dispatched, so they are reserved until then.
+    fn torpedoes_left(&self) -> u32 {
+        let fired = self.events.queued(|e| matches!(e, GameEvent::TorpedoFired { .. }));
+        self.torpedoes.saturating_sub(fired as u32)
+    }
+
+    /// Random number generator for everything that shapes the run
+    fn rng(&self) -> RefMut<'_, GameRng> {
+        self.rng.borrow_mut()
+    }
+
+    /// Number of players that did not sink yet
+    fn players_left(&self) -> usize {
+        self.sunk_at[..self.players].iter().filter(|s| s.is_none()).count()
+    }
+
+    /// Score of a single player in versus: the number of frames it stayed afloat
+    fn player_score(&self, player: usize) -> i64 {
+        self.sunk_at[player].unwrap_or(self.fc)
+    }
+
+    /// Lives and rewinding are second chances, which one-hit modes, versus and races do not give
+    fn second_chance(&self) -> bool {
+        !self.mode.one_hit() && self.players == 1 && self.net.is_none()
+    }
+
+    /// True when the player can go back in time after a crash: a second chance in a mode where the sub can crash
+    fn can_rewind(&self) -> bool {
+        self.mode.dangers() && self.second_chance()
+    }
+
+    /// Timed runs end when the clock runs out
+    fn time_up(&self) -> bool {
+        self.mode.time_limit().is_some_and(|limit| self.fc >= limit)
+    }
+
+    /// Player with the highest score in versus, unless it is a draw
+    fn leader(&self) -> Option<usize> {
+        let scores: Vec<_> = (0..self.players).map(|p| self.player_score(p)).collect();
+        let best = *scores.iter().max()?;
+
+        match scores.iter().filter(|s| **s == best).count() {
+            1 => scores.iter().position(|s| *s == best),
+            _ => None,
+        }
+    }
+
+    /// Last checkpoint the player passed in the current run
+    fn checkpoint(&self) -> u32 {
+        self.pipes / CHECKPOINT_PIPES
+    }
+
+    /// Keeps track of ammo and bonus points
+    fn on_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::RunStarted => {
+                self.torpedoes = START_TORPEDOES;
+                self.bonus = 0;
+                self.pipes = 0;
+                self.distance = 0;
+                self.lives = START_LIVES;
+                self.sunk_at = [None; MAX_PLAYERS];
+                self.forces = [(0.0, 0.0); MAX_PLAYERS];
+            }
+            GameEvent::Sunk { player } => self.sunk_at[*player] = Some(self.fc),
+            GameEvent::Respawned { .. } => self.sunk_at = [None; MAX_PLAYERS],
+            GameEvent::PipePassed => {
+                self.pipes += 1;
+                self.reached_checkpoint = self.reached_checkpoint.max(self.checkpoint());
+            }
+            GameEvent::LifeLost => self.lives = self.lives.saturating_sub(1),
+            GameEvent::AmmoCollected { count } => self.torpedoes = (self.torpedoes + count).min(MAX_TORPEDOES),
+            GameEvent::TorpedoFired { .. } => self.torpedoes = self.torpedoes.saturating_sub(1),
+            GameEvent::Destroyed { points } => self.bonus += points,
+            _ => {}
+        }
+    }
 }
 
 /// A collidable can return bounding boxes which can be used for collision detection
@@ -60,6 +269,54 @@ trait Renderable {
     fn switch_theme(&mut self, theme: &Theme);
     /// Reset the object to its initial state for a new game
     fn reset(&mut self);
+    /// Start the randomness of the object over from the seed of the run, when it shapes the run (if applicable)
+    fn reseed(&mut self, _seed: u64) {}
+    /// Handle an input event during the game (if applicable)
+    fn handle_event(&mut self, _event: &Event, _state: &GameState) {}
+    /// Returns the object as collidable, when it takes part in collision detection
+    fn as_collidable(&self) -> Option<&dyn Collidable> {
+        None
+    }
+    /// Called when the object collided against an obstacle. Returns true when the object crashed.
+    fn on_collision(&mut self, _state: &GameState) -> bool {
+        true
+    }
+    /// Called with the bounding boxes of the players, so collectibles can check if they are picked up
+    fn collect(&mut self, _players: &[Rect], _state: &GameState) {}
+    /// Called when a projectile hits the given area. Returns true when the projectile hit something.
+    fn hit(&mut self, _area: Rect, _state: &GameState) -> bool {
+        false
+    }
+    /// React to an event that happened in the game (if applicable)
+    fn on_event(&mut self, _event: &GameEvent, _state: &GameState) {}
+    /// Number of the player, when the object is one of the players
+    fn player(&self) -> Option<usize> {
+        None
+    }
+    /// Force (x, y) this object exerts on anything in the given area
+    fn force_at(&self, _area: Rect) -> (f32, f32) {
+        (0.0, 0.0)
+    }
+    /// Copy of the state of the object without any textures, so it can be restored later (if applicable)
+    fn snapshot(&self) -> Option<Box<dyn Any>> {
+        None
+    }
+    /// Restores the state from an earlier snapshot
+    fn restore(&mut self, _snapshot: Box<dyn Any>) {}
+    /// Writes the state of the object to a save file (if applicable)
+    fn save(&self, _w: &mut SaveWriter) {}
+    /// Reads the state written by save
+    fn load(&mut self, _r: &mut SaveReader) -> Result<(), String> {
+        Ok(())
+    }
+    /// Writes what spectators need to draw the object. Unless the object says otherwise, that is what it saves.
+    fn broadcast(&self, w: &mut SaveWriter) {
+        self.save(w);
+    }
+    /// Reads the state written by broadcast
+    fn watch(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        self.load(r)
+    }
 }
 
 /// It's hard to work with fonts due to the lifetime issues, so we wrap it in a struct
@@ -77,6 +334,20 @@ impl<'a> TTF<'a> {
 }
 
 pub fn main() -> Result<(), String> {
+    // Command line: "--broadcast <address>" lets spectators watch the game, "--spectate <address>" watches a game,
+    // and "--verify <file>" replays a run without a window and checks its score
+    let args: Vec<String> = std::env::args().collect();
+    let arg = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+
+    let verifying = match arg("--verify") {
+        Some(path) => {
+            sdl2::hint::set("SDL_VIDEODRIVER", "dummy");
+            let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            Some(Replay::decode(&contents)?)
+        }
+        None => None,
+    };
+
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
 
@@ -93,64 +364,205 @@ pub fn main() -> Result<(), String> {
     let texture_creator = canvas.texture_creator();
     let (w, h) = canvas.output_size().unwrap();
 
+    let theme = ThemeSwitcher::new(load_biomes(BIOME_DIR)?);
+
     let mut state = GameState {
         game_started: false,
         game_over: false,
         fc: 0,
-        high_score: 0,
-        x_speed: 3,
+        high_scores: [0; ALL_MODES.len()],
+        practice_high_score: 0,
+        x_speed: theme.biome().x_speed,
         window_height: h,
         window_width: w,
-        theme: ThemeSwitcher::new(),
-        run_count: 0
+        theme,
+        run_count: 0,
+        settings: Settings::load(SETTINGS_FILE)?,
+        events: EventBus::new(),
+        power_ups: PowerUps::new(),
+        oxygen: Oxygen::new(),
+        player: None,
+        player_areas: vec![],
+        torpedoes: START_TORPEDOES,
+        bonus: 0,
+        forces: [(0.0, 0.0); MAX_PLAYERS],
+        rng: RefCell::new(GameRng::new(0)),
+        seed: 0,
+        pipes: 0,
+        practice: false,
+        lives: START_LIVES,
+        start_checkpoint: 0,
+        reached_checkpoint: 0,
+        rewind: Rewind::new(),
+        mode: GameMode::Classic,
+        distance: 0,
+        daily: None,
+        daily_results: DailyResults::load(DAILY_FILE)?,
+        players: 1,
+        sunk_at: [None; MAX_PLAYERS],
+        winner: None,
+        net: None,
+        finished_race: None,
+        standings: None,
+        broadcast: None,
+        replay: None,
+        submitted: None,
     };
 
     // Create all renderables for the game
-    let mut actors = Actors {
-        sub: Sub::new(100, 100, &texture_creator),
-        bubbles: Bubbles::new(MAX_BUBBLES, &texture_creator, w, h),
-        pipes: Pipes::new(&texture_creator),
-        background: Background::new(&state, &texture_creator),
-        score: Score::new(&ttf)
-    };
+    let both = [Scene::Title, Scene::Game];
+    let terrain_tag = if state.settings.terrain_collision { CollisionTag::Obstacle } else { CollisionTag::None };
+
+    let mut actors = Actors::new();
+    actors.add(layer::BACKGROUND, &both, terrain_tag, Box::new(Background::new(w, h)));
+    actors.add(layer::FAUNA, &both, CollisionTag::None, Box::new(Fauna::new(&texture_creator)));
+    actors.add(layer::FAUNA, &[Scene::Game], CollisionTag::None, Box::new(Currents::new()));
+    actors.add(layer::ENEMIES, &[Scene::Game], CollisionTag::Obstacle, Box::new(Enemies::new(&texture_creator)));
+    actors.add(layer::OBSTACLES, &[Scene::Game], CollisionTag::Obstacle, Box::new(Pipes::new(&texture_creator)));
+    actors.add(layer::OBSTACLES, &[Scene::Game], CollisionTag::Collectible, Box::new(Pickups::new(&texture_creator)));
+    actors.add(layer::PLAYER, &both, CollisionTag::Player, Box::new(Sub::new(0, PLAYER_X, 100, &texture_creator)));
+    actors.add(layer::PLAYER, &both, CollisionTag::Player, Box::new(Sub::new(1, PLAYER_X, 180, &texture_creator)));
+    actors.add(layer::PLAYER, &[Scene::Game], CollisionTag::None, Box::new(Ghosts::new(&texture_creator)));
+    actors.add(layer::PLAYER, &[Scene::Game], CollisionTag::Projectile, Box::new(Torpedoes::new(&texture_creator)));
+    actors.add(layer::FOREGROUND, &both, CollisionTag::Collectible, Box::new(Bubbles::new(MAX_BUBBLES, &texture_creator, w, h)));
+    actors.add(layer::HUD, &[Scene::Game], CollisionTag::None, Box::new(Score::new(&ttf)));
 
     // we need to switch theme so all actors can find / set the correct colors
-    actors.switch_theme(&state.theme.current());
+    actors.switch_theme(state.theme.current());
+
+    if let Some(replay) = &verifying {
+        return verify(replay, &mut state, &mut actors);
+    }
+    if let Some(address) = arg("--spectate") {
+        let mut event_pump = sdl_context.event_pump()?;
+        return spectate(address, &mut state, &mut canvas, &mut event_pump, &mut actors);
+    }
+    if let Some(address) = arg("--broadcast") {
+        state.broadcast = Some(Broadcaster::new(address)?);
+    }
 
     loop {
         let mut event_pump = sdl_context.event_pump()?;
 
         // Do pregame / title screen
-        do_pregame(&mut state, &mut canvas, &ttf, &mut event_pump, &mut actors)?;
+        let start = do_pregame(&mut state, &mut canvas, &ttf, &mut event_pump, &mut actors)?;
 
-        actors.reset();
+        let resumed = start == Start::SavedRun && match load_run(&mut state, &mut actors) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("Cannot continue the saved run: {}", e);
+                false
+            }
+        };
 
-        // run a game
-        do_game(&mut state, &mut canvas, &mut event_pump, &mut actors)?;
+        if !resumed {
+            // Ranked runs explore a new ocean, practice runs replay the ocean of the last run, and the daily challenge
+            // is the same ocean for everyone playing today. Racers get the ocean of the host.
+            if let Some(seed) = state.net.as_ref().and_then(|n| n.seed()) {
+                state.seed = seed;
+                state.reached_checkpoint = 0;
+            } else if state.daily.is_some() {
+                let day = daily::today();
+                state.daily = Some(day);
+                state.seed = daily::seed(day);
+                state.mode = GameMode::Classic;
+                state.reached_checkpoint = 0;
+            } else if !state.practice {
+                state.seed = state.settings.seed.unwrap_or_else(rand::random);
+                state.reached_checkpoint = 0;
+            }
+            start_run(&mut state, &mut actors);
+
+            let checkpoint = state.start_checkpoint;
+            if state.practice && checkpoint > 0 {
+                respawn(&mut state, &mut actors, checkpoint);
+            }
+        }
 
-        // Update high score and reinitialize game
-        if state.fc > state.high_score {
-            state.high_score = state.fc;
+        // Only ranked runs of a single player can go on the leaderboard, and only when they are played in one go
+        state.submitted = None;
+        state.replay = None;
+        state.standings = None;
+        if !resumed && !state.practice && state.players == 1 && state.net.is_none() {
+            state.replay = Some(Replay::new(&state.settings, state.seed, state.mode));
         }
 
-        state.run_count += 1;
+        // run a game, which either ends in a crash or is saved for later
+        let finished = do_game(&mut state, &mut canvas, &mut event_pump, &mut actors)?;
+
+        if finished {
+            state.events.emit(GameEvent::RunEnded { score: state.score() });
+
+            // Update high score
+            let score = state.score();
+            let high_score = if state.practice { &mut state.practice_high_score } else { &mut state.high_scores[state.mode.index()] };
+            *high_score = (*high_score).max(score);
+
+            record_daily(&mut state);
+
+            // The last sub afloat wins a versus run
+            state.winner = state.leader();
+
+            if let Some(replay) = state.replay.as_mut() {
+                replay.score = score;
+            }
+
+            state.run_count += 1;
+        } else {
+            state.replay = None;
+        }
+
+        // Our race is over as soon as our own sub sank. The others see it sink, instead of seeing it vanish, and we
+        // stay in the race until they are done as well.
+        if let Some(mut net) = state.net.take() {
+            if finished {
+                net.sink(state.distance);
+                state.finished_race = Some(net);
+            } else {
+                net.leave();
+            }
+        }
+
+        // Reinitialize game
         state.fc = 0;
         state.game_started = false;
         state.game_over = false;
+
+        // Back to the surface
+        if state.theme.update(state.fc) != ThemeChange::Unchanged {
+            switch_theme(&state, &mut actors);
+            announce_theme(&state);
+        }
+        state.x_speed = state.theme.biome().x_speed;
     }
 }
 
-// Returns Ok(true) when the game can begin. Returns ok(false) when we want to quit
-fn do_pregame(state: &mut GameState, canvas: &mut WindowCanvas, ttf: &TTF, event_pump: &mut sdl2::EventPump, actors: &mut Actors) -> Result<bool, String> {
+/// How the player wants to start the game from the title screen
+#[derive(PartialEq)]
+enum Start {
+    NewRun,
+    SavedRun,
+    /// Race other players over the network
+    Race,
+}
+
+// Returns how the game should begin, once the player is ready
+fn do_pregame(state: &mut GameState, canvas: &mut WindowCanvas, ttf: &TTF, event_pump: &mut sdl2::EventPump, actors: &mut Actors) -> Result<Start, String> {
     let theme = state.theme.current();
 
-    actors.sub.reset();
+    actors.reset();
 
     // Create message texture
-    let s = if state.run_count == 0 { "Press <space> to begin" } else { "You sunk. Press <space> to try again" };
+    let s = match state.winner {
+        _ if state.run_count == 0 => "Press <space> to begin".to_string(),
+        _ if state.finished_race.is_some() => "You sunk. Press <space> once the race is over".to_string(),
+        Some(player) if state.players > 1 => format!("Player {} wins! Press <space> for a rematch", player + 1),
+        None if state.players > 1 => "It's a draw! Press <space> for a rematch".to_string(),
+        _ => "You sunk. Press <space> to try again".to_string(),
+    };
 
     let surface = ttf.font
-        .render(s)
+        .render(s.as_str())
         .blended(Color::RGBA(theme.text.0, theme.text.1, theme.text.2, 255))
         .map_err(|e| e.to_string())?;
 
@@ -159,97 +571,870 @@ fn do_pregame(state: &mut GameState, canvas: &mut WindowCanvas, ttf: &TTF, event
         .create_texture_from_surface(&surface)
         .map_err(|e| e.to_string())?;
 
+    let saved_run = Path::new(SAVE_FILE).exists();
+    let continue_texture = if saved_run {
+        let surface = ttf.font
+            .render("Press <enter> to continue your saved run")
+            .blended(Color::RGBA(theme.text.0, theme.text.1, theme.text.2, 255))
+            .map_err(|e| e.to_string())?;
+
+        Some(texture_creator.create_texture_from_surface(&surface).map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+
     loop {
+        let mut leaderboard = false;
+
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. } => {
+                    if let Some(net) = &state.finished_race {
+                        net.leave();
+                    }
                     exit(0);
                 }
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    if let Some(net) = state.finished_race.take() {
+                        net.leave();
+                    }
+                }
+                // Nothing new starts while the last race is still on, as the host relays the other racers
+                _ if state.finished_race.is_some() => {}
                 Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
                     state.game_started = true;
-                    return Ok(true);
+                    return Ok(Start::NewRun);
+                }
+                Event::KeyDown { keycode: Some(Keycode::Return), .. } if saved_run => {
+                    state.game_started = true;
+                    return Ok(Start::SavedRun);
                 }
                 Event::KeyDown { keycode: Some(Keycode::T), .. } => {
                     state.theme.next();
 
-                    actors.switch_theme(&state.theme.current());
+                    switch_theme(state, actors);
+                    announce_theme(state);
+                }
+                // Practice can only be started after a run, as it replays the ocean of that run
+                Event::KeyDown { keycode: Some(Keycode::P), .. } if state.run_count > 0 => {
+                    state.practice = !state.practice;
+                    state.daily = None;
+                    state.players = 1;
+                    state.start_checkpoint = state.start_checkpoint.min(state.reached_checkpoint);
+                }
+                Event::KeyDown { keycode: Some(Keycode::Up), .. } if state.practice => {
+                    state.start_checkpoint = (state.start_checkpoint + 1).min(state.reached_checkpoint);
+                }
+                Event::KeyDown { keycode: Some(Keycode::Down), .. } if state.practice => {
+                    state.start_checkpoint = state.start_checkpoint.saturating_sub(1);
+                }
+                Event::KeyDown { keycode: Some(Keycode::M), .. } if state.daily.is_none() => {
+                    state.mode = state.mode.next();
+                }
+                // The daily challenge is a ranked run with the classic rules
+                Event::KeyDown { keycode: Some(Keycode::D), .. } => {
+                    state.daily = if state.daily.is_some() { None } else { Some(daily::today()) };
+                    state.practice = false;
+                    state.players = 1;
+                    state.mode = GameMode::Classic;
+                }
+                // Versus is a race between the players, without practice or the daily challenge
+                Event::KeyDown { keycode: Some(Keycode::Num2), .. } => {
+                    state.players = if state.players > 1 { 1 } else { MAX_PLAYERS };
+                    state.practice = false;
+                    state.daily = None;
+                }
+                // Network races are ranked runs with the classic rules, with a single sub on every screen
+                Event::KeyDown { keycode: Some(key @ (Keycode::H | Keycode::J)), .. } => {
+                    let net = if key == Keycode::H {
+                        NetRace::host(state.settings.race_port)
+                    } else {
+                        NetRace::join(&state.settings.race_host)
+                    };
+
+                    match net {
+                        Ok(net) => {
+                            state.net = Some(net);
+                            state.practice = false;
+                            state.daily = None;
+                            state.players = 1;
+                            state.mode = GameMode::Classic;
+                        }
+                        Err(e) => eprintln!("Cannot start a network race: {}", e),
+                    }
+                }
+                Event::KeyDown { keycode: Some(Keycode::S), .. } if state.replay.is_some() => {
+                    submit_score(state);
+                }
+                Event::KeyDown { keycode: Some(Keycode::L), .. } => {
+                    leaderboard = true;
                 }
                 _ => {}
             }
         }
 
+        if leaderboard {
+            do_leaderboard(state, canvas, ttf, event_pump, actors)?;
+        }
+
+        if state.net.is_some() && do_lobby(state, canvas, ttf, event_pump, actors)? {
+            state.game_started = true;
+            return Ok(Start::Race);
+        }
+        follow_race(state);
+
         // Update stuff
-        actors.update(&state);
+        actors.update(state);
+        actors.dispatch_events(state);
 
         // Render stuff
-        actors.background.render(&state, canvas)?;
-        actors.sub.render(&state, canvas)?;
-        actors.bubbles.render(&state, canvas)?;
+        actors.render(Scene::Title, state, canvas)?;
 
         canvas.copy(&title_texture, None, Rect::new(250, 100, 300, 60))?;
+        if state.run_count > 0 {
+            render_practice_info(state, canvas, ttf)?;
+        }
+        render_mode_info(state, canvas, ttf)?;
+        render_daily_info(state, canvas, ttf)?;
+        render_versus_info(state, canvas, ttf)?;
+        render_title_line(state, canvas, ttf, "Press <h> to host a network race, or <j> to join one", 340)?;
+        render_leaderboard_info(state, canvas, ttf)?;
+        render_race_info(state, canvas, ttf)?;
+        if let Some(texture) = &continue_texture {
+            canvas.copy(texture, None, Rect::new(250, 230, 300, 20))?;
+        }
+        canvas.present();
+        std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+/// Waits in the lobby of a network race until the host starts it. Returns Ok(true) when the race starts, and Ok(false)
+/// when the player left the lobby.
+fn do_lobby(state: &mut GameState, canvas: &mut WindowCanvas, ttf: &TTF, event_pump: &mut sdl2::EventPump, actors: &mut Actors) -> Result<bool, String> {
+    loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } => {
+                    if let Some(net) = &state.net {
+                        net.leave();
+                    }
+                    exit(0);
+                }
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    if let Some(net) = state.net.take() {
+                        net.leave();
+                    }
+                    return Ok(false);
+                }
+                Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
+                    let seed = state.settings.seed.unwrap_or_else(rand::random);
+                    if let Some(net) = state.net.as_mut().filter(|n| n.is_host()) {
+                        net.start(seed);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let Some(net) = state.net.as_mut() else {
+            return Ok(false);
+        };
+        net.poll();
+        if net.seed().is_some() {
+            return Ok(true);
+        }
+
+        let s = match net.id() {
+            _ if net.is_host() => format!("Hosting a race on port {} with {} racers. Press <space> to start", state.settings.race_port, net.racers()),
+            Some(id) => format!("Joined the race as racer {}. Waiting for the host to start", id + 1),
+            None => format!("Joining the race at {}...", state.settings.race_host),
+        };
+
+        actors.update(state);
+        actors.dispatch_events(state);
+
+        actors.render(Scene::Title, state, canvas)?;
+        render_title_line(state, canvas, ttf, &s, 170)?;
+        render_title_line(state, canvas, ttf, "Press <escape> to leave the race", 200)?;
+        canvas.present();
+        std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+/// Stays in the race our sub sank in until every other racer sank or left, and keeps the standings up to date
+fn follow_race(state: &mut GameState) {
+    let Some(net) = state.finished_race.as_mut() else {
+        return;
+    };
+    net.wait();
+
+    let standings: Vec<String> = net.standings()
+        .iter()
+        .enumerate()
+        .map(|(i, (id, distance))| match net.id() {
+            Some(our_id) if our_id == *id => format!("{}. You - {}", i + 1, distance),
+            _ => format!("{}. Racer {} - {}", i + 1, id + 1, distance),
+        })
+        .collect();
+    state.standings = Some(standings.join("   "));
+
+    if net.diving() == 0 {
+        net.leave();
+        state.finished_race = None;
+    }
+}
+
+/// Shows how far every racer got in the last race, and who is still diving
+fn render_race_info(state: &GameState, canvas: &mut WindowCanvas, ttf: &TTF) -> Result<(), String> {
+    if let Some(standings) = &state.standings {
+        render_title_line(state, canvas, ttf, &format!("Race: {}", standings), 420)?;
+    }
+    if let Some(net) = &state.finished_race {
+        let s = format!("Waiting for {} racers to sink. Press <escape> to leave the race", net.diving());
+        render_title_line(state, canvas, ttf, &s, 445)?;
+    }
+
+    Ok(())
+}
+
+/// Shows whether the next run is a practice run, and at which checkpoint it starts
+fn render_practice_info(state: &GameState, canvas: &mut WindowCanvas, ttf: &TTF) -> Result<(), String> {
+    let s = if state.practice {
+        format!("Practice from checkpoint {} of {} (<up>/<down>, <p> for ranked)", state.start_checkpoint, state.reached_checkpoint)
+    } else {
+        "Press <p> to practice this run".to_string()
+    };
+
+    render_title_line(state, canvas, ttf, &s, 170)
+}
+
+/// Shows whether the last run can be submitted to the online leaderboard, and what the server made of it
+fn render_leaderboard_info(state: &GameState, canvas: &mut WindowCanvas, ttf: &TTF) -> Result<(), String> {
+    let s = match &state.replay {
+        Some(replay) => format!("Press <s> to submit your score of {}, or <l> for the online leaderboard", replay.score),
+        None => "Press <l> for the online leaderboard".to_string(),
+    };
+    render_title_line(state, canvas, ttf, &s, 365)?;
+
+    if let Some(answer) = &state.submitted {
+        render_title_line(state, canvas, ttf, answer, 390)?;
+    }
+
+    Ok(())
+}
+
+/// Sends the replay of the last run to the leaderboard server, which plays it again to check the score. A score can
+/// only be submitted once, but it can be tried again when the server cannot be reached.
+fn submit_score(state: &mut GameState) {
+    let Some(replay) = &state.replay else {
+        return;
+    };
+
+    match http::post(&state.settings.leaderboard_url, "/scores", &replay.encode()) {
+        Ok(answer) => {
+            state.submitted = Some(answer.trim().to_string());
+            state.replay = None;
+        }
+        Err(e) => state.submitted = Some(format!("Cannot submit your score: {}", e)),
+    }
+}
+
+/// Shows the best scores on the leaderboard server in the mode of the next run, until the player goes back
+fn do_leaderboard(state: &mut GameState, canvas: &mut WindowCanvas, ttf: &TTF, event_pump: &mut sdl2::EventPump, actors: &mut Actors) -> Result<(), String> {
+    // Every line of the leaderboard is "score<tab>mode<tab>name<tab>seed", best scores first
+    let lines = match http::get(&state.settings.leaderboard_url, "/scores") {
+        Ok(body) => {
+            let scores: Vec<String> = body.lines()
+                .filter_map(|line| {
+                    let mut fields = line.split('\t');
+                    let (score, mode, name) = (fields.next()?, fields.next()?, fields.next()?);
+                    (mode == state.mode.name()).then_some((score, name))
+                })
+                .take(LEADERBOARD_LINES)
+                .enumerate()
+                .map(|(i, (score, name))| format!("{}. {} - {}", i + 1, name, score))
+                .collect();
+
+            if scores.is_empty() { vec!["No scores yet. Be the first!".to_string()] } else { scores }
+        }
+        Err(e) => vec![format!("Cannot reach the leaderboard: {}", e)],
+    };
+    let title = format!("Online leaderboard - {}", state.mode.name());
+
+    loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } => {
+                    exit(0);
+                }
+                Event::KeyDown { keycode: Some(Keycode::Escape | Keycode::L), .. } => {
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        follow_race(state);
+
+        actors.update(state);
+        actors.dispatch_events(state);
+
+        actors.render(Scene::Title, state, canvas)?;
+        render_title_line(state, canvas, ttf, &title, 130)?;
+        for (i, line) in lines.iter().enumerate() {
+            render_title_line(state, canvas, ttf, line, 170 + i as i32 * 25)?;
+        }
+        render_title_line(state, canvas, ttf, "Press <escape> to go back", 450)?;
+        canvas.present();
+        std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+/// Shows the mode of the next run, and its high score
+fn render_mode_info(state: &GameState, canvas: &mut WindowCanvas, ttf: &TTF) -> Result<(), String> {
+    let s = format!("Mode: {} - Hi-Score: {} (<m> to change)", state.mode.name(), state.high_scores[state.mode.index()]);
+
+    render_title_line(state, canvas, ttf, &s, 200)
+}
+
+/// Shows the daily challenge of today, and how the player did on earlier days
+fn render_daily_info(state: &GameState, canvas: &mut WindowCanvas, ttf: &TTF) -> Result<(), String> {
+    let Some(day) = state.daily else {
+        let s = format!("Press <d> for the daily challenge of {}", daily::date(daily::today()));
+        return render_title_line(state, canvas, ttf, &s, 260);
+    };
+
+    let s = match state.daily_results.score(day) {
+        Some(score) => format!("Daily challenge of {} - scored {}, this run does not count (<d> to leave)", daily::date(day), score),
+        None => format!("Daily challenge of {} - only the first attempt counts! (<d> to leave)", daily::date(day)),
+    };
+    render_title_line(state, canvas, ttf, &s, 260)?;
+
+    if let Some((date, score)) = state.daily_results.best() {
+        let s = format!("Played {} daily challenges, best score {} on {}", state.daily_results.played(), score, date);
+        render_title_line(state, canvas, ttf, &s, 285)?;
+    }
+
+    Ok(())
+}
+
+/// Shows whether the next run is a versus run, and the keys of the players
+fn render_versus_info(state: &GameState, canvas: &mut WindowCanvas, ttf: &TTF) -> Result<(), String> {
+    let s = if state.players > 1 {
+        "Versus - player 1: <space>/<f>, player 2: <up>/<down> (<2> for one player)"
+    } else {
+        "Press <2> for two player versus"
+    };
+
+    render_title_line(state, canvas, ttf, s, 315)
+}
+
+/// Draws a centered line of text on the title screen
+fn render_title_line(state: &GameState, canvas: &mut WindowCanvas, ttf: &TTF, s: &str, y: i32) -> Result<(), String> {
+    let theme = state.theme.current();
+
+    let surface = ttf.font
+        .render(s)
+        .blended(Color::RGBA(theme.text.0, theme.text.1, theme.text.2, 255))
+        .map_err(|e| e.to_string())?;
+
+    let texture_creator = canvas.texture_creator();
+    let texture = texture_creator
+        .create_texture_from_surface(&surface)
+        .map_err(|e| e.to_string())?;
+
+    let width = surface.width() * 20 / surface.height();
+    canvas.copy(&texture, None, Rect::new(400 - width as i32 / 2, y, width, 20))?;
+
+    Ok(())
+}
+
+/// Starts a new run on the ocean of the seed of the run. Everything a run starts with follows from the seed and the
+/// mode, which is how the leaderboard server starts a replay.
+fn start_run(state: &mut GameState, actors: &mut Actors) {
+    state.rng().reseed(state.seed);
+
+    actors.reset();
+    actors.reseed(state.seed);
+    state.events.emit(GameEvent::RunStarted);
+    actors.dispatch_events(state);
+}
+
+/// Rebuilds the ocean of the current run up to the given checkpoint, without drawing it, and puts the sub back in
+/// the water. Everything that shapes the ocean comes from the seeded rng, so it is the same ocean as before.
+fn respawn(state: &mut GameState, actors: &mut Actors, checkpoint: u32) {
+    actors.reset();
+    actors.reseed(state.seed);
+    state.rng().reseed(state.seed);
+    state.fc = 0;
+    state.pipes = 0;
+    state.distance = 0;
+
+    // The sub sits this out, so it cannot crash or collect anything
+    state.game_started = false;
+    while state.checkpoint() < checkpoint {
+        state.fc += 1;
+
+        update_biome(state, actors);
+        state.distance += state.x_speed as i64;
+
+        actors.update(state);
+        actors.dispatch_events(state);
+    }
+    state.game_started = true;
+
+    state.events.emit(GameEvent::Respawned { checkpoint });
+    actors.dispatch_events(state);
+}
+
+/// Stores the score of the daily challenge. Only the first attempt counts, so the score so far is stored as soon as
+/// the player leaves the run, even when it is saved for later. Otherwise the player could leave a bad attempt and try
+/// again.
+fn record_daily(state: &mut GameState) {
+    let Some(day) = state.daily else {
+        return;
+    };
+
+    if let Err(e) = state.daily_results.record(day, state.score()) {
+        eprintln!("Cannot store the daily result: {}", e);
+    }
+}
+
+/// Writes the current run to the save file, so it can be continued from the title screen
+fn save_run(state: &GameState, actors: &Actors) -> Result<(), String> {
+    let mut w = SaveWriter::new();
+    write_run(state, actors, &mut w);
+
+    w.write(SAVE_FILE)
+}
+
+/// Writes everything about the current run: the game state, the random sequence and all actors
+fn write_run(state: &GameState, actors: &Actors, w: &mut SaveWriter) {
+    write_state(state, w);
+
+    w.label("seed");
+    state.seed.save(w);
+    state.rng().save(w);
+
+    actors.save(w);
+}
+
+/// Writes the game state of the current run, which is also what spectators need for the HUD
+fn write_state(state: &GameState, w: &mut SaveWriter) {
+    w.label("run");
+    (state.fc, state.bonus).save(w);
+    (state.torpedoes, state.pipes).save(w);
+    state.rewind.budget().save(w);
+    save_variant(&ALL_MODES, &state.mode, w);
+    state.distance.save(w);
+    state.daily.save(w);
+    (state.players, state.sunk_at.to_vec()).save(w);
+
+    w.label("practice");
+    (state.practice, state.lives).save(w);
+    (state.start_checkpoint, state.reached_checkpoint).save(w);
+
+    w.label("power_ups");
+    state.power_ups.save(w);
+    w.label("oxygen");
+    state.oxygen.save(w);
+}
+
+/// Continues the run from the save file. The save file is removed, so a run can only be continued once.
+fn load_run(state: &mut GameState, actors: &mut Actors) -> Result<(), String> {
+    let contents = fs::read_to_string(SAVE_FILE).map_err(|e| format!("{}: {}", SAVE_FILE, e))?;
+    fs::remove_file(SAVE_FILE).map_err(|e| format!("{}: {}", SAVE_FILE, e))?;
+
+    let mut r = SaveReader::new(&contents)?;
+    actors.reset();
+    read_run(state, actors, &mut r)?;
+
+    // Dive back into the biome we were in
+    if state.mode.theme_switching() {
+        state.theme.update(state.fc);
+    }
+    switch_theme(state, actors);
+    announce_theme(state);
+    state.x_speed = state.mode.x_speed(state.theme.biome().x_speed, state.fc);
+
+    Ok(())
+}
+
+/// Reads everything about a run, as written by write_run
+fn read_run(state: &mut GameState, actors: &mut Actors, r: &mut SaveReader) -> Result<(), String> {
+    read_state(state, r)?;
+
+    r.expect("seed")?;
+    state.seed = Save::load(r)?;
+    *state.rng() = Save::load(r)?;
+
+    actors.load(r)?;
+    state.player = actors.player_position();
+    state.player_areas = actors.player_areas();
+
+    Ok(())
+}
+
+/// Reads the game state written by write_state
+fn read_state(state: &mut GameState, r: &mut SaveReader) -> Result<(), String> {
+    r.expect("run")?;
+    (state.fc, state.bonus) = Save::load(r)?;
+    (state.torpedoes, state.pipes) = Save::load(r)?;
+    state.rewind.resume(Save::load(r)?);
+    state.mode = load_variant(&ALL_MODES, r)?;
+    state.distance = Save::load(r)?;
+    state.daily = Save::load(r)?;
+    let sunk_at: Vec<Option<i64>>;
+    (state.players, sunk_at) = Save::load(r)?;
+    if !(1..=MAX_PLAYERS).contains(&state.players) {
+        return Err(format!("invalid number of players {} in save file", state.players));
+    }
+    state.sunk_at = sunk_at.try_into().map_err(|_| "invalid number of players in save file".to_string())?;
+
+    r.expect("practice")?;
+    (state.practice, state.lives) = Save::load(r)?;
+    (state.start_checkpoint, state.reached_checkpoint) = Save::load(r)?;
+
+    r.expect("power_ups")?;
+    state.power_ups = Save::load(r)?;
+    r.expect("oxygen")?;
+    state.oxygen = Save::load(r)?;
+
+    Ok(())
+}
+
+/// Sends the current frame to everyone watching
+fn broadcast(state: &mut GameState, actors: &Actors) {
+    if state.broadcast.is_none() {
+        return;
+    }
+
+    let mut w = SaveWriter::new();
+    write_state(state, &mut w);
+    actors.broadcast(&mut w);
+    let frame = w.into_line();
+
+    if let Some(broadcast) = state.broadcast.as_mut() {
+        broadcast.send(&frame);
+    }
+}
+
+/// Watches a game broadcast by another instance, until the broadcast ends or the player closes the window
+fn spectate(address: &str, state: &mut GameState, canvas: &mut WindowCanvas, event_pump: &mut sdl2::EventPump, actors: &mut Actors) -> Result<(), String> {
+    let mut spectator = Spectator::connect(address)?;
+    state.game_started = true;
+
+    loop {
+        for event in event_pump.poll_iter() {
+            if let Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } = event {
+                return Ok(());
+            }
+        }
+
+        // Only draw what was broadcast: everything moves because the game it comes from moves
+        if let Some(frame) = spectator.latest()? {
+            let mut r = SaveReader::new(&frame)?;
+            read_state(state, &mut r)?;
+            actors.watch(&mut r)?;
+            state.player = actors.player_position();
+
+            if state.mode.theme_switching() && state.theme.update(state.fc) != ThemeChange::Unchanged {
+                switch_theme(state, actors);
+            }
+        }
+
+        actors.render(Scene::Game, state, canvas)?;
         canvas.present();
         std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
 }
 
+/// Plays a replay without drawing it, as fast as possible, and checks that it ends with the score it claims. Prints
+/// the verified score with the mode, the name of the player and the seed, for the leaderboard server.
+fn verify(replay: &Replay, state: &mut GameState, actors: &mut Actors) -> Result<(), String> {
+    // Only the settings of the leaderboard count. Everything else the run starts with follows from the seed and mode.
+    if replay.physics != state.settings.physics || replay.terrain_collision != state.settings.terrain_collision {
+        return Err("the replay is played with other settings than the leaderboard uses".into());
+    }
+
+    state.seed = replay.seed;
+    state.mode = replay.mode;
+    state.practice = false;
+    state.players = 1;
+    start_run(state, actors);
+    state.game_started = true;
+
+    let mut inputs = replay.inputs().iter().peekable();
+    let last_input = replay.inputs().last().map(|(fc, _)| *fc).unwrap_or(0);
+
+    while !state.game_over {
+        start_frame(state, actors);
+
+        while let Some((_, input)) = inputs.next_if(|(fc, _)| *fc == state.fc) {
+            match input.event() {
+                Some(event) => actors.handle_event(&event, state),
+                None => {
+                    state.game_over = true;
+                    state.game_started = false;
+                }
+            }
+        }
+
+        if finish_frame(state, actors) || state.time_up() {
+            break;
+        }
+        // A run in which nothing can sink the sub only ends when the player ends it
+        if state.fc > last_input + MAX_IDLE_FRAMES {
+            return Err("the replay does not end".into());
+        }
+    }
+
+    if inputs.next().is_some() {
+        return Err("the replay goes on after the run ended".into());
+    }
+    if state.score() != replay.score {
+        return Err(format!("the replay scores {}, not {}", state.score(), replay.score));
+    }
+
+    println!("verified\t{}\t{}\t{}\t{}", replay.score, state.mode.name(), replay.name, replay.seed);
+    Ok(())
+}
+
+/// Runs the game until the sub sinks. Returns Ok(true) when the run is over, and Ok(false) when the player left
+/// halfway and the run was saved for later.
 fn do_game(state: &mut GameState, canvas: &mut WindowCanvas, event_pump: &mut sdl2::EventPump, actors: &mut Actors) -> Result<bool, String> {
     while !state.game_over {
-        state.fc += 1;
+        start_frame(state, actors);
 
         // Poll for events
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. } => {
+                    record_daily(state);
+                    match &state.net {
+                        Some(net) => net.leave(),
+                        None => if let Err(e) = save_run(state, actors) {
+                            eprintln!("Cannot save the run: {}", e);
+                        },
+                    }
                     exit(0);
                 }
-                Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
-                    actors.sub.velocity = actors.sub.jump_strength;
+                // A race goes on without us, so it cannot be saved for later
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } if state.net.is_none() => {
+                    record_daily(state);
+                    save_run(state, actors)?;
+                    state.replay = None;
+                    return Ok(false);
                 }
                 Event::KeyDown { keycode: Some(Keycode::C), .. } => {
                     state.game_over = true;
                     state.game_started = false;
                 }
-                Event::KeyDown { keycode: Some(Keycode::T), .. } => {
+                Event::KeyDown { keycode: Some(Keycode::T), .. } if state.mode.theme_switching() => {
                     state.theme.next();
 
-                    actors.switch_theme(&state.theme.current());
+                    switch_theme(state, actors);
+                    announce_theme(state);
                 }
                 _ => {}
             }
+
+            if let Some(replay) = state.replay.as_mut() {
+                replay.record(state.fc, &event);
+            }
+            actors.handle_event(&event, state);
         }
 
-        actors.update(state);
+        let sunk = finish_frame(state, actors);
+
+        // Spectators see the frame as it is, crash included
+        broadcast(state, actors);
+
+        if sunk {
+            if state.practice && state.second_chance() && state.lives > 0 {
+                respawn(state, actors, state.checkpoint());
+                continue;
+            }
+            // Going back in time changes the run, so the replay no longer adds up
+            if state.can_rewind() && rewind(state, canvas, event_pump, actors)? {
+                state.replay = None;
+                continue;
+            }
+
+            state.game_over = true;
+            return Ok(true)
+        }
 
-        if collision_detected(&actors.sub, &actors.pipes) {
+        if state.time_up() {
             state.game_over = true;
             return Ok(true)
         }
 
+        let snapshot = Snapshot::take(state, actors);
+        state.rewind.record(snapshot);
+
         // Draw everything
-        actors.background.render(&state, canvas)?;
-        actors.pipes.render(&state, canvas)?;
-        actors.sub.render(&state, canvas)?;
-        actors.bubbles.render(&state, canvas)?;
-        actors.score.render(&state, canvas)?;
+        actors.render(Scene::Game, state, canvas)?;
+
+        canvas.present();
+        std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+
+    Ok(true)
+}
+
+/// Starts a new frame: the clock ticks and the ocean moves on, before the input of the players is handled
+fn start_frame(state: &mut GameState, actors: &mut Actors) {
+    state.fc += 1;
+
+    // Move through the biomes as we progress
+    update_biome(state, actors);
+
+    state.power_ups.update();
+    if state.mode.dangers() {
+        state.oxygen.update();
+    }
+    if state.power_ups.active(PowerUp::SlowMotion) {
+        state.x_speed = (state.x_speed / 2).max(1);
+    }
+    state.distance += state.x_speed as i64;
+}
+
+/// Finishes a frame after the input of the players was handled: everything moves, and crashes are detected. Returns
+/// true when the run sank.
+fn finish_frame(state: &mut GameState, actors: &mut Actors) -> bool {
+    actors.update(state);
+    state.player = actors.player_position();
+    state.player_areas = actors.player_areas();
+    state.forces = [(0.0, 0.0); MAX_PLAYERS];
+    for (player, area) in &state.player_areas {
+        state.forces[*player] = actors.force_at(*area);
+    }
+
+    // Tell the other racers where we are, and hear where they are
+    if let Some(net) = state.net.as_mut() {
+        net.poll();
+        if let Some((_, area)) = state.player_areas.first() {
+            net.send_state(state.distance, area.y(), state.sunk_at[0].is_some());
+        }
+    }
+
+    actors.check_pickups(state);
+    actors.check_hits(state);
+    let collided = state.mode.dangers() && actors.check_collisions(state);
+
+    let suffocated = state.oxygen.empty();
+    if suffocated {
+        state.events.emit(GameEvent::OutOfAir);
+    }
+    if state.practice && state.second_chance() && (collided || suffocated) {
+        state.events.emit(GameEvent::LifeLost);
+    }
+
+    actors.dispatch_events(state);
+
+    // In versus, the run goes on as long as any of the subs is afloat
+    suffocated || (collided && state.players_left() == 0)
+}
+
+/// After a crash, the player can hold <r> to go back in time. Returns true when the run continues from an earlier
+/// moment, and false when the player did not rewind in time, or there is nothing left to rewind.
+fn rewind(state: &mut GameState, canvas: &mut WindowCanvas, event_pump: &mut sdl2::EventPump, actors: &mut Actors) -> Result<bool, String> {
+    if !state.rewind.available() {
+        return Ok(false);
+    }
+
+    state.rewind.mode = RewindMode::Prompt;
+    let mut waited = 0;
+
+    loop {
+        for event in event_pump.poll_iter() {
+            if let Event::Quit { .. } = event {
+                exit(0);
+            }
+        }
+
+        let holding = event_pump.keyboard_state().is_scancode_pressed(Scancode::R);
+        let rewinding = state.rewind.mode == RewindMode::Rewinding;
+
+        let snapshot = if holding { state.rewind.pop() } else { None };
+
+        match snapshot {
+            Some(snapshot) => {
+                snapshot.restore(state, actors);
+                state.rewind.mode = RewindMode::Rewinding;
+
+                update_biome(state, actors);
+                actors.dispatch_events(state);
+            }
+            // Let go, or at the end of the history or budget
+            _ if rewinding => break,
+            _ => {
+                waited += 1;
+                if waited > REWIND_PROMPT {
+                    state.rewind.mode = RewindMode::Off;
+                    return Ok(false);
+                }
+            }
+        }
+
+        actors.render(Scene::Game, state, canvas)?;
+        if state.rewind.mode == RewindMode::Rewinding {
+            render_vhs(state, canvas)?;
+        }
 
         canvas.present();
         std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
 
+    state.rewind.mode = RewindMode::Off;
     Ok(true)
 }
 
-/// Returns true if the submarines collided against a pipe (or the ground / surface)
-fn collision_detected(sub: &Sub, pipes: &Pipes) -> bool {
-    let binding = sub.get_bounding_boxes();
-    let bb = binding.first().unwrap();
+/// Tints the screen like an old video tape being rewound
+fn render_vhs(state: &GameState, canvas: &mut WindowCanvas) -> Result<(), String> {
+    let (w, h) = (state.window_width, state.window_height);
+    canvas.set_blend_mode(BlendMode::Blend);
 
-    let pipes_bb = pipes.get_bounding_boxes();
-    for pipe in pipes_bb {
-        if bb.has_intersection(pipe) {
-            return true;
+    canvas.set_draw_color(Color::RGBA(110, 40, 150, 60));
+    canvas.fill_rect(Rect::new(0, 0, w, h))?;
+
+    // Scanlines
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, 50));
+    for y in (0..h as i32).step_by(3) {
+        canvas.fill_rect(Rect::new(0, y, w, 1))?;
+    }
+
+    // Tracking band rolling over the screen
+    let band = (state.fc * 7).rem_euclid(h as i64) as i32;
+    canvas.set_draw_color(Color::RGBA(255, 255, 255, 40));
+    canvas.fill_rect(Rect::new(0, band, w, 8))?;
+
+    canvas.set_blend_mode(BlendMode::None);
+    Ok(())
+}
+
+/// Moves through the biomes as the run progresses, unless the mode keeps the run in one biome, and sets the speed.
+/// The colors blend into the next biome over a number of frames, but the new biome is only announced once.
+fn update_biome(state: &mut GameState, actors: &mut Actors) {
+    if state.mode.theme_switching() {
+        match state.theme.update(state.fc) {
+            ThemeChange::Unchanged => {}
+            ThemeChange::Blending => switch_theme(state, actors),
+            ThemeChange::NewBiome => {
+                switch_theme(state, actors);
+                announce_theme(state);
+            }
         }
     }
+    state.x_speed = state.mode.x_speed(state.theme.biome().x_speed, state.fc);
+}
 
-    false
-}
\ No newline at end of file
+/// Lets all actors know the theme has changed
+fn switch_theme(state: &GameState, actors: &mut Actors) {
+    actors.switch_theme(state.theme.current());
+}
+
+/// Lets everyone know the player picked other colors, or the sub entered another biome
+fn announce_theme(state: &GameState) {
+    state.events.emit(GameEvent::ThemeChanged { biome: state.theme.biome().name.clone() });
+}
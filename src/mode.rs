@@ -0,0 +1,96 @@
+/// Length of a time attack run: one minute
+const TIME_ATTACK_FRAMES: i64 = 60 * 60;
+
+/// In time attack, the game speeds up every this many frames
+const SPEED_RAMP_FRAMES: i64 = 10 * 60;
+
+/// Hardcore gaps are this fraction of the normal size
+const HARDCORE_GAP_SCALE: f32 = 0.75;
+
+/// Rules of a run, chosen on the title screen
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameMode {
+    /// Survive as long as possible
+    Classic,
+    /// Get as far as possible in a minute, while the game speeds up
+    TimeAttack,
+    /// Nothing to crash into, just bubbles and fauna
+    Zen,
+    /// Narrow gaps, a single biome and no second chances
+    Hardcore,
+}
+
+pub const ALL_MODES: [GameMode; 4] = [
+    GameMode::Classic,
+    GameMode::TimeAttack,
+    GameMode::Zen,
+    GameMode::Hardcore,
+];
+
+impl GameMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Classic => "Classic",
+            GameMode::TimeAttack => "Time Attack",
+            GameMode::Zen => "Zen",
+            GameMode::Hardcore => "Hardcore",
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        ALL_MODES.iter().position(|m| m == self).unwrap()
+    }
+
+    /// The mode after this one on the title screen
+    pub fn next(&self) -> GameMode {
+        ALL_MODES[(self.index() + 1) % ALL_MODES.len()]
+    }
+
+    /// Number of frames a run lasts, if it is timed
+    pub fn time_limit(&self) -> Option<i64> {
+        match self {
+            GameMode::TimeAttack => Some(TIME_ATTACK_FRAMES),
+            _ => None,
+        }
+    }
+
+    /// Scrolling speed at the given frame, based on the speed of the biome
+    pub fn x_speed(&self, biome_speed: i32, fc: i64) -> i32 {
+        match self {
+            GameMode::TimeAttack => biome_speed + (fc / SPEED_RAMP_FRAMES) as i32,
+            GameMode::Zen => (biome_speed * 2 / 3).max(1),
+            _ => biome_speed,
+        }
+    }
+
+    /// False when there is nothing that can sink the sub: no obstacles, no collisions and no running out of air
+    pub fn dangers(&self) -> bool {
+        *self != GameMode::Zen
+    }
+
+    /// Size of the gaps between the pipes, relative to the size set by the biome
+    pub fn gap_scale(&self) -> f32 {
+        match self {
+            GameMode::Hardcore => HARDCORE_GAP_SCALE,
+            _ => 1.0,
+        }
+    }
+
+    /// False when the run stays in the biome it started in
+    pub fn theme_switching(&self) -> bool {
+        *self != GameMode::Hardcore
+    }
+
+    /// True when the first hit sinks the sub: shields, rewinding and practice lives do not help
+    pub fn one_hit(&self) -> bool {
+        *self == GameMode::Hardcore
+    }
+
+    /// Score of a run. Time attack counts the distance travelled, the other modes the time survived.
+    pub fn score(&self, fc: i64, distance: i64, bonus: i64) -> i64 {
+        match self {
+            GameMode::TimeAttack => distance / 10 + bonus,
+            _ => fc + bonus,
+        }
+    }
+}
@@ -0,0 +1,420 @@
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::str::FromStr;
+
+/// First word of every message, so other versions of the game (or anything else) are ignored
+const PROTOCOL: &str = "flappydive-race-1";
+
+/// Racers that were not heard of for this many frames have left the race
+const TIMEOUT_FRAMES: u32 = 180;
+
+/// Frames between two hellos of a racer that is waiting for the host
+const HELLO_FRAMES: u32 = 30;
+
+/// Times the last state of a sunk sub is sent, as there are no more states after it to make up for a lost one
+const SUNK_REPEATS: usize = 3;
+
+/// Messages between the racers. Every message is a single datagram of words separated by spaces. Nothing is sent
+/// twice on purpose: positions are sent every frame, and a racer keeps saying hello until the race started.
+#[derive(Debug, PartialEq)]
+enum Message {
+    /// A racer wants to join, or is still waiting for the race to start
+    Hello,
+    /// The host accepted a racer, and gave it an id
+    Welcome { id: u32 },
+    /// The race starts in the ocean of the given seed
+    Start { seed: u64 },
+    /// Where the sub of a racer is
+    State { id: u32, distance: i64, y: i32, sunk: bool },
+    /// A racer left the race
+    Bye { id: u32 },
+}
+
+impl Message {
+    fn encode(&self) -> String {
+        let body = match self {
+            Message::Hello => "hello".to_string(),
+            Message::Welcome { id } => format!("welcome {}", id),
+            Message::Start { seed } => format!("start {}", seed),
+            Message::State { id, distance, y, sunk } => format!("state {} {} {} {}", id, distance, y, sunk),
+            Message::Bye { id } => format!("bye {}", id),
+        };
+
+        format!("{} {}", PROTOCOL, body)
+    }
+
+    fn decode(s: &str) -> Option<Message> {
+        let mut words = s.split_whitespace();
+        if words.next()? != PROTOCOL {
+            return None;
+        }
+
+        let msg = match words.next()? {
+            "hello" => Message::Hello,
+            "welcome" => Message::Welcome { id: parse(words.next())? },
+            "start" => Message::Start { seed: parse(words.next())? },
+            "state" => Message::State {
+                id: parse(words.next())?,
+                distance: parse(words.next())?,
+                y: parse(words.next())?,
+                sunk: parse(words.next())?,
+            },
+            "bye" => Message::Bye { id: parse(words.next())? },
+            _ => return None,
+        };
+
+        Some(msg)
+    }
+}
+
+fn parse<T: FromStr>(word: Option<&str>) -> Option<T> {
+    word?.parse().ok()
+}
+
+/// Sub of another racer, as last heard of
+#[derive(Clone, Copy, Debug)]
+pub struct Ghost {
+    pub id: u32,
+    /// Distance travelled since the start of the race, in pixels
+    pub distance: i64,
+    pub y: i32,
+    pub sunk: bool,
+    silent: u32,
+}
+
+/// Racer that joined the host
+struct Peer {
+    id: u32,
+    addr: SocketAddr,
+    silent: u32,
+}
+
+/// Race against other players over the network. Every racer dives through the same ocean, and sees the others as
+/// ghosts. There is no lockstep: every racer runs its own game, and only tells the others where its sub is.
+///
+/// The host relays the positions of all racers, so racers only need to know the address of the host.
+pub struct NetRace {
+    socket: UdpSocket,
+    /// Address of the host, or None when we are the host
+    host: Option<SocketAddr>,
+    /// Our id in the race: 0 for the host, and given by the host to everyone else
+    id: Option<u32>,
+    /// Racers that joined (host only)
+    peers: Vec<Peer>,
+    next_id: u32,
+    ghosts: Vec<Ghost>,
+    /// Seed of the race, once it started
+    seed: Option<u64>,
+    ticks: u32,
+    /// Where our sub was when it was last sent, so the others see it sink where it was
+    last_distance: i64,
+    last_y: i32,
+    /// Ids and final distances of the other racers whose sub sank. They are kept when the racer leaves.
+    results: Vec<(u32, i64)>,
+}
+
+impl NetRace {
+    /// Hosts a race on the given port
+    pub fn host(port: u16) -> Result<Self, String> {
+        let socket = UdpSocket::bind(("0.0.0.0", port)).map_err(|e| format!("cannot host on port {}: {}", port, e))?;
+
+        Self::new(socket, None, Some(0))
+    }
+
+    /// Joins the race hosted at the given address ("host:port")
+    pub fn join(address: &str) -> Result<Self, String> {
+        let host = address.to_socket_addrs()
+            .map_err(|e| format!("{}: {}", address, e))?
+            .find(|a| a.is_ipv4())
+            .ok_or_else(|| format!("{}: unknown host", address))?;
+        let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(|e| e.to_string())?;
+
+        let race = Self::new(socket, Some(host), None)?;
+        race.send(&Message::Hello, host);
+        Ok(race)
+    }
+
+    fn new(socket: UdpSocket, host: Option<SocketAddr>, id: Option<u32>) -> Result<Self, String> {
+        socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            socket,
+            host,
+            id,
+            peers: vec![],
+            next_id: 1,
+            ghosts: vec![],
+            seed: None,
+            ticks: 0,
+            last_distance: 0,
+            last_y: 0,
+            results: vec![],
+        })
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.host.is_none()
+    }
+
+    /// Our id in the race, once the host accepted us
+    pub fn id(&self) -> Option<u32> {
+        self.id
+    }
+
+    /// Number of racers including ourselves, as far as we know
+    pub fn racers(&self) -> usize {
+        if self.is_host() { self.peers.len() + 1 } else { self.ghosts.len() + 1 }
+    }
+
+    /// Seed of the race, once it started
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn ghosts(&self) -> &[Ghost] {
+        &self.ghosts
+    }
+
+    /// Starts the race (host only)
+    pub fn start(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        for peer in &self.peers {
+            self.send(&Message::Start { seed }, peer.addr);
+        }
+    }
+
+    /// Handles all messages that arrived since the last poll. Should be called every frame.
+    pub fn poll(&mut self) {
+        let mut buf = [0u8; 512];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, from)) => {
+                    if let Some(msg) = std::str::from_utf8(&buf[..len]).ok().and_then(Message::decode) {
+                        self.receive(msg, from);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                // Some systems report that an earlier datagram could not be delivered. Nothing we can do about it.
+                Err(_) => break,
+            }
+        }
+
+        self.ticks += 1;
+        for ghost in self.ghosts.iter_mut() {
+            ghost.silent += 1;
+        }
+        self.ghosts.retain(|g| g.silent < TIMEOUT_FRAMES);
+        for peer in self.peers.iter_mut() {
+            peer.silent += 1;
+        }
+        self.peers.retain(|p| p.silent < TIMEOUT_FRAMES);
+
+        // Keep knocking until the host lets us in and the race started, in case a message got lost
+        if let Some(host) = self.host {
+            if (self.id.is_none() || self.seed.is_none()) && self.ticks.is_multiple_of(HELLO_FRAMES) {
+                self.send(&Message::Hello, host);
+            }
+        }
+    }
+
+    fn receive(&mut self, msg: Message, from: SocketAddr) {
+        match self.host {
+            // Racers only listen to the host
+            Some(host) if host != from => {}
+            Some(_) => match msg {
+                Message::Welcome { id } => self.id = Some(id),
+                Message::Start { seed } => self.seed = Some(seed),
+                Message::State { id, distance, y, sunk } if Some(id) != self.id => self.update_ghost(id, distance, y, sunk),
+                Message::Bye { id } => self.ghosts.retain(|g| g.id != id),
+                _ => {}
+            },
+            None => {
+                let id = match self.peers.iter_mut().find(|p| p.addr == from) {
+                    Some(peer) => {
+                        peer.silent = 0;
+                        peer.id
+                    }
+                    None if msg == Message::Hello => {
+                        let id = self.next_id;
+                        self.next_id += 1;
+                        self.peers.push(Peer { id, addr: from, silent: 0 });
+                        id
+                    }
+                    None => return,
+                };
+
+                match msg {
+                    Message::Hello => {
+                        self.send(&Message::Welcome { id }, from);
+                        if let Some(seed) = self.seed {
+                            self.send(&Message::Start { seed }, from);
+                        }
+                    }
+                    Message::State { distance, y, sunk, .. } => {
+                        self.update_ghost(id, distance, y, sunk);
+                        self.relay(&Message::State { id, distance, y, sunk }, from);
+                    }
+                    Message::Bye { .. } => {
+                        self.peers.retain(|p| p.id != id);
+                        self.ghosts.retain(|g| g.id != id);
+                        self.relay(&Message::Bye { id }, from);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn update_ghost(&mut self, id: u32, distance: i64, y: i32, sunk: bool) {
+        if sunk && !self.results.iter().any(|(i, _)| *i == id) {
+            self.results.push((id, distance));
+        }
+
+        match self.ghosts.iter_mut().find(|g| g.id == id) {
+            Some(ghost) => *ghost = Ghost { id, distance, y, sunk, silent: 0 },
+            None => self.ghosts.push(Ghost { id, distance, y, sunk, silent: 0 }),
+        }
+    }
+
+    /// Tells the other racers where our sub is
+    pub fn send_state(&mut self, distance: i64, y: i32, sunk: bool) {
+        let Some(id) = self.id else {
+            return;
+        };
+        (self.last_distance, self.last_y) = (distance, y);
+
+        let msg = Message::State { id, distance, y, sunk };
+        match self.host {
+            Some(host) => self.send(&msg, host),
+            None => self.relay(&msg, None),
+        }
+    }
+
+    /// Tells the other racers our sub sank. It stays in their race as a sunk ghost until it times out, instead of
+    /// disappearing at once.
+    pub fn sink(&mut self, distance: i64) {
+        for _ in 0..SUNK_REPEATS {
+            self.send_state(distance, self.last_y, true);
+        }
+    }
+
+    /// Keeps our sunk sub in the race while the others are still diving. Should be called every frame after our sub
+    /// sank: the host relays the others for as long as the race goes on, and the others keep hearing of us.
+    pub fn wait(&mut self) {
+        self.poll();
+        self.send_state(self.last_distance, self.last_y, true);
+    }
+
+    /// Number of other racers whose sub is still diving
+    pub fn diving(&self) -> usize {
+        self.ghosts.iter().filter(|g| !g.sunk).count()
+    }
+
+    /// Ids and distances of all racers whose sub sank, ourselves included, furthest first
+    pub fn standings(&self) -> Vec<(u32, i64)> {
+        let mut standings = self.results.clone();
+        if let Some(id) = self.id {
+            standings.push((id, self.last_distance));
+        }
+        standings.sort_by_key(|(_, distance)| std::cmp::Reverse(*distance));
+
+        standings
+    }
+
+    /// Tells the other racers we are gone
+    pub fn leave(&self) {
+        let Some(id) = self.id else {
+            return;
+        };
+
+        match self.host {
+            Some(host) => self.send(&Message::Bye { id }, host),
+            None => self.relay(&Message::Bye { id }, None),
+        }
+    }
+
+    /// Sends a message to all racers that joined, except the one it came from (host only)
+    fn relay(&self, msg: &Message, from: impl Into<Option<SocketAddr>>) {
+        let from = from.into();
+        for peer in self.peers.iter().filter(|p| Some(p.addr) != from) {
+            self.send(msg, peer.addr);
+        }
+    }
+
+    fn send(&self, msg: &Message, to: SocketAddr) {
+        // Datagrams get lost anyway, so a failed send is just another lost datagram
+        let _ = self.socket.send_to(msg.encode().as_bytes(), to);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_survive_encoding() {
+        let messages = [
+            Message::Hello,
+            Message::Welcome { id: 3 },
+            Message::Start { seed: u64::MAX },
+            Message::State { id: 2, distance: 123_456, y: -40, sunk: true },
+            Message::Bye { id: 7 },
+        ];
+
+        for msg in messages {
+            assert_eq!(Message::decode(&msg.encode()), Some(msg));
+        }
+    }
+
+    #[test]
+    fn other_protocols_and_broken_messages_are_ignored() {
+        assert_eq!(Message::decode("hello"), None);
+        assert_eq!(Message::decode("flappydive-race-0 hello"), None);
+        assert_eq!(Message::decode(&format!("{} state 1 2", PROTOCOL)), None);
+        assert_eq!(Message::decode(&format!("{} welcome x", PROTOCOL)), None);
+        assert_eq!(Message::decode(&format!("{} dance", PROTOCOL)), None);
+        assert_eq!(Message::decode(""), None);
+    }
+
+    #[test]
+    fn racers_see_each_other_through_the_host() {
+        let mut host = NetRace::host(0).unwrap();
+        let port = host.socket.local_addr().unwrap().port();
+        let mut racer = NetRace::join(&format!("127.0.0.1:{}", port)).unwrap();
+
+        let wait = |race: &mut NetRace, done: &dyn Fn(&NetRace) -> bool| {
+            for _ in 0..100 {
+                race.poll();
+                if done(race) {
+                    return;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            panic!("no answer");
+        };
+
+        wait(&mut host, &|h| h.racers() == 2);
+        wait(&mut racer, &|r| r.id() == Some(1));
+
+        host.start(42);
+        wait(&mut racer, &|r| r.seed() == Some(42));
+
+        host.send_state(500, 120, false);
+        wait(&mut racer, &|r| r.ghosts().iter().any(|g| g.id == 0 && g.distance == 500));
+
+        host.sink(600);
+        wait(&mut racer, &|r| r.ghosts().iter().any(|g| g.id == 0 && g.sunk && g.y == 120));
+        assert_eq!(racer.diving(), 0);
+
+        // The host keeps relaying after its own sub sank, and the result of the host stays after it left
+        racer.send_state(800, 90, false);
+        wait(&mut host, &|h| h.diving() == 1);
+        racer.sink(900);
+        wait(&mut host, &|h| h.diving() == 0);
+        assert_eq!(host.standings(), [(1, 900), (0, 600)]);
+
+        host.leave();
+        wait(&mut racer, &|r| r.ghosts().is_empty());
+        assert_eq!(racer.standings(), [(1, 900), (0, 600)]);
+    }
+}
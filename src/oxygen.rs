@@ -0,0 +1,55 @@
+use crate::events::GameEvent;
+use crate::save::{Save, SaveReader, SaveWriter};
+
+/// Oxygen in a full tank
+pub const MAX_OXYGEN: f32 = 100.0;
+
+/// Oxygen used per tick. A full tank lasts 20 seconds.
+const DRAIN: f32 = MAX_OXYGEN / 1200.0;
+
+/// Oxygen gained from a single air pocket
+const AIR_POCKET: f32 = 25.0;
+
+/// Oxygen left in the tank of the sub
+#[derive(Clone)]
+pub struct Oxygen {
+    level: f32,
+}
+
+impl Oxygen {
+    pub fn new() -> Self {
+        Self { level: MAX_OXYGEN }
+    }
+
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    /// Returns true when the sub ran out of air
+    pub fn empty(&self) -> bool {
+        self.level <= 0.0
+    }
+
+    /// Breathes for one tick
+    pub fn update(&mut self) {
+        self.level = (self.level - DRAIN).max(0.0);
+    }
+
+    pub fn on_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::RunStarted | GameEvent::Respawned { .. } => self.level = MAX_OXYGEN,
+            GameEvent::AirCollected => self.level = (self.level + AIR_POCKET).min(MAX_OXYGEN),
+            _ => {}
+        }
+    }
+}
+
+impl Save for Oxygen {
+    fn save(&self, w: &mut SaveWriter) {
+        self.level.save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        Ok(Self { level: Save::load(r)? })
+    }
+}
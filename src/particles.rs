@@ -0,0 +1,417 @@
+use std::cell::RefCell;
+use std::ops::Range;
+use rand::Rng;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Texture, WindowCanvas};
+use crate::animation::{Animation, AnimationPlayer};
+use crate::rng::GameRng;
+use crate::save::{Save, SaveReader, SaveWriter};
+
+/// How the particles of an emitter look
+#[derive(Clone, Copy)]
+pub enum ParticleShape {
+    /// One of the textures of the emitter, picked at random for each particle
+    Textures,
+    /// A filled square in the color given to render()
+    Square,
+}
+
+/// Configuration of a particle emitter
+#[derive(Clone)]
+pub struct EmitterConfig {
+    /// Number of particles spawned per tick (can be fractional)
+    pub rate: f32,
+    /// Maximum number of particles alive at the same time
+    pub max_particles: usize,
+    /// Lifetime of a particle in ticks
+    pub lifetime: Range<u32>,
+    /// Initial horizontal velocity
+    pub velocity_x: Range<f32>,
+    /// Initial vertical velocity
+    pub velocity_y: Range<f32>,
+    /// Fraction of the velocity that is kept each tick (1.0 is no drag)
+    pub drag: f32,
+    /// Vertical acceleration per tick. Negative values rise, positive values sink.
+    pub buoyancy: f32,
+    /// Size (scale for textures, pixels for squares) at the start and end of the life of a particle
+    pub size: (f32, f32),
+    /// Alpha at the start and end of the life of a particle
+    pub alpha: (u8, u8),
+    /// When true, particles scroll along with the world
+    pub scroll: bool,
+    pub shape: ParticleShape,
+    /// Chance (0.0 - 1.0) that a particle can be collected. Collectible particles are drawn larger and brighter.
+    pub collectible: f64,
+}
+
+impl Default for EmitterConfig {
+    fn default() -> Self {
+        Self {
+            rate: 0.0,
+            max_particles: 100,
+            lifetime: 30..60,
+            velocity_x: 0.0..0.0,
+            velocity_y: 0.0..0.0,
+            drag: 1.0,
+            buoyancy: 0.0,
+            size: (1.0, 1.0),
+            alpha: (255, 255),
+            scroll: true,
+            shape: ParticleShape::Square,
+            collectible: 0.0,
+        }
+    }
+}
+
+/// Random value in the range. Empty ranges (ie: 0.0..0.0) always return their start.
+fn pick<T: rand::distr::uniform::SampleUniform + PartialOrd + Copy>(rng: &mut impl Rng, range: &Range<T>) -> T {
+    if range.start < range.end { rng.gen_range(range.clone()) } else { range.start }
+}
+
+#[derive(Clone)]
+struct Particle {
+    x: f32,
+    y: f32,
+    vx: f32,
+    vy: f32,
+    age: u32,
+    lifetime: u32,
+    texture_idx: usize,
+    // Death animation, played when the lifetime has ended and the texture is animated
+    dying: Option<AnimationPlayer>,
+    collectible: bool,
+}
+
+impl Particle {
+    /// How far the particle is in its life (0.0 - 1.0)
+    fn progress(&self) -> f32 {
+        (self.age as f32 / self.lifetime.max(1) as f32).min(1.0)
+    }
+}
+
+/// Particles of an emitter at some moment, without its textures
+#[derive(Clone)]
+pub struct EmitterState {
+    particles: Vec<Particle>,
+    accumulator: f32,
+    rng: GameRng,
+}
+
+/// Collectible particles are this much larger than normal particles
+const COLLECTIBLE_SCALE: f32 = 1.6;
+
+/// Spawns, moves and renders particles. New particles are spawned inside the spawn area.
+pub struct Emitter<'a> {
+    pub config: EmitterConfig,
+    particles: Vec<Particle>,
+    spawn_area: Rect,
+    // Fractional particles that still need to be spawned
+    accumulator: f32,
+    // Particles can be collected, so they get their own rng, which is saved with them
+    rng: GameRng,
+    // Textures are changed while rendering (alpha over life), so they need interior mutability
+    textures: RefCell<Vec<Texture<'a>>>,
+    // Animation for each texture. Animated textures play their animation when a particle dies.
+    animations: Vec<Animation>,
+    // Color mod of the textures
+    tint: (u8, u8, u8),
+}
+
+impl<'a> Emitter<'a> {
+    pub fn new(config: EmitterConfig) -> Self {
+        Self::with_textures(config, vec![])
+    }
+
+    pub fn with_textures(config: EmitterConfig, sprites: Vec<(Texture<'a>, Animation)>) -> Self {
+        let (textures, animations) = sprites.into_iter().unzip();
+
+        Self {
+            config,
+            particles: Vec::new(),
+            spawn_area: Rect::new(0, 0, 1, 1),
+            accumulator: 0.0,
+            rng: GameRng::new(rand::random()),
+            textures: RefCell::new(textures),
+            animations,
+            tint: (255, 255, 255),
+        }
+    }
+
+    /// Tints all textures of the emitter
+    pub fn set_color_mod(&mut self, color: (u8, u8, u8)) {
+        self.tint = color;
+        for texture in self.textures.get_mut().iter_mut() {
+            texture.set_color_mod(color.0, color.1, color.2);
+        }
+    }
+
+    /// Copy of all the particles, so they can be put back later
+    pub fn state(&self) -> EmitterState {
+        EmitterState { particles: self.particles.clone(), accumulator: self.accumulator, rng: self.rng.clone() }
+    }
+
+    pub fn restore(&mut self, state: EmitterState) {
+        self.particles = state.particles;
+        self.accumulator = state.accumulator;
+        self.rng = state.rng;
+    }
+
+    /// Removes all particles and starts the random sequence over, so the same particles are spawned again
+    pub fn reseed(&mut self, seed: u64) {
+        self.particles.clear();
+        self.accumulator = 0.0;
+        self.rng.reseed(seed);
+    }
+
+    /// Reads the particles of a saved emitter state. Every particle has to fit the textures of this emitter.
+    pub fn load(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        let state = EmitterState::load(r)?;
+        self.check(&state.particles)?;
+
+        self.restore(state);
+        Ok(())
+    }
+
+    /// Writes only what is needed to draw the particles, for spectators. They never move the particles themselves,
+    /// so speeds and the random sequence are left out, and positions are rounded to whole pixels.
+    pub fn broadcast(&self, w: &mut SaveWriter) {
+        self.particles.len().save(w);
+        for p in &self.particles {
+            (p.x as i32, p.y as i32).save(w);
+            (p.age, p.lifetime).save(w);
+            (p.texture_idx, p.collectible).save(w);
+            p.dying.save(w);
+        }
+    }
+
+    /// Reads the particles written by broadcast
+    pub fn watch(&mut self, r: &mut SaveReader) -> Result<(), String> {
+        let len = usize::load(r)?;
+        let particles = (0..len)
+            .map(|_| {
+                let (x, y): (i32, i32) = Save::load(r)?;
+                let (age, lifetime) = Save::load(r)?;
+                let (texture_idx, collectible) = Save::load(r)?;
+                let dying = Save::load(r)?;
+                Ok(Particle { x: x as f32, y: y as f32, vx: 0.0, vy: 0.0, age, lifetime, texture_idx, dying, collectible })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        self.check(&particles)?;
+
+        self.particles = particles;
+        Ok(())
+    }
+
+    /// Checks that particles read from a save file or a broadcast fit the textures of this emitter
+    fn check(&self, particles: &[Particle]) -> Result<(), String> {
+        let textured = matches!(self.config.shape, ParticleShape::Textures);
+
+        for p in particles {
+            let animation = self.animations.get(p.texture_idx);
+            if animation.is_none() && (textured || p.dying.is_some()) {
+                return Err(format!("invalid particle texture {} in save file", p.texture_idx));
+            }
+            if let (Some(player), Some(animation)) = (&p.dying, animation) {
+                player.check(animation)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves the area in which new particles are spawned
+    pub fn set_spawn_area(&mut self, area: Rect) {
+        self.spawn_area = area;
+    }
+
+    /// Spawns a number of particles at once
+    pub fn burst(&mut self, count: usize) {
+        for _ in 0..count {
+            if self.particles.len() >= self.config.max_particles {
+                break;
+            }
+            self.spawn();
+        }
+    }
+
+    fn spawn(&mut self) {
+        let rng = &mut self.rng;
+        let c = &self.config;
+        let a = self.spawn_area;
+
+        let texture_idx = match c.shape {
+            ParticleShape::Textures => rng.gen_range(0..self.animations.len().max(1)),
+            ParticleShape::Square => 0,
+        };
+
+        self.particles.push(Particle {
+            x: rng.gen_range(a.left()..a.right().max(a.left() + 1)) as f32,
+            y: rng.gen_range(a.top()..a.bottom().max(a.top() + 1)) as f32,
+            vx: pick(rng, &c.velocity_x),
+            vy: pick(rng, &c.velocity_y),
+            age: 0,
+            lifetime: pick(rng, &c.lifetime),
+            texture_idx,
+            dying: None,
+            collectible: rng.gen_bool(c.collectible),
+        });
+    }
+
+    /// Size of a particle at this moment
+    fn scale(&self, p: &Particle) -> f32 {
+        let (s0, s1) = self.config.size;
+        let size = s0 + (s1 - s0) * p.progress();
+
+        if p.collectible { size * COLLECTIBLE_SCALE } else { size }
+    }
+
+    /// Pops all collectible particles inside the given area. Returns the number of particles collected.
+    pub fn collect(&mut self, area: Rect) -> usize {
+        let mut collected = 0;
+
+        for idx in 0..self.particles.len() {
+            let p = &self.particles[idx];
+            if !p.collectible || p.dying.is_some() {
+                continue;
+            }
+
+            let (w, h) = match self.config.shape {
+                ParticleShape::Textures => self.animations[p.texture_idx].frame_size(),
+                ParticleShape::Square => (1, 1),
+            };
+            let scale = self.scale(p);
+            let rect = Rect::new(p.x as i32, p.y as i32, (w as f32 * scale).max(1.0) as u32, (h as f32 * scale).max(1.0) as u32);
+            if !rect.has_intersection(area) {
+                continue;
+            }
+
+            let p = &mut self.particles[idx];
+            p.collectible = false;
+            p.lifetime = p.age;
+            if self.animations.get(p.texture_idx).is_some_and(|a| a.frame_count() > 1) {
+                p.dying = Some(AnimationPlayer::new());
+            }
+            collected += 1;
+        }
+
+        collected
+    }
+
+    /// Moves all particles, removes dead ones and spawns new ones
+    pub fn update(&mut self, x_speed: f32) {
+        self.accumulator += self.config.rate;
+        while self.accumulator >= 1.0 {
+            self.accumulator -= 1.0;
+            if self.particles.len() < self.config.max_particles {
+                self.spawn();
+            }
+        }
+
+        for p in self.particles.iter_mut() {
+            if self.config.scroll {
+                p.x -= x_speed;
+            }
+
+            if let Some(player) = &mut p.dying {
+                player.update(&self.animations[p.texture_idx]);
+                continue;
+            }
+
+            p.vx *= self.config.drag;
+            p.vy = p.vy * self.config.drag + self.config.buoyancy;
+            p.x += p.vx;
+            p.y += p.vy;
+            p.age += 1;
+
+            let animated = self.animations.get(p.texture_idx).is_some_and(|a| a.frame_count() > 1);
+            if p.age >= p.lifetime && animated {
+                p.dying = Some(AnimationPlayer::new());
+            }
+        }
+
+        self.particles.retain(|p| {
+            let alive = match &p.dying {
+                Some(player) => !player.finished(),
+                None => p.age < p.lifetime,
+            };
+            alive && p.x > -50.0 && p.y > -50.0
+        });
+    }
+
+    /// Renders all particles. Squares are drawn in the given color, textures use their color mod.
+    pub fn render(&self, canvas: &mut WindowCanvas, color: (u8, u8, u8)) -> Result<(), String> {
+        let mut textures = self.textures.borrow_mut();
+        let (a0, a1) = self.config.alpha;
+
+        for p in &self.particles {
+            let t = p.progress();
+            let size = self.scale(p);
+            let alpha = (a0 as f32 + (a1 as f32 - a0 as f32) * t) as u8;
+
+            match self.config.shape {
+                ParticleShape::Textures => {
+                    let animation = &self.animations[p.texture_idx];
+                    let src = match &p.dying {
+                        Some(player) => player.frame_rect(animation),
+                        None => animation.first_frame(),
+                    };
+
+                    let texture = &mut textures[p.texture_idx];
+                    texture.set_alpha_mod(alpha);
+                    let w = (src.width() as f32 * size).max(1.0) as u32;
+                    let h = (src.height() as f32 * size).max(1.0) as u32;
+
+                    if p.collectible {
+                        // Halfway towards white, so they stand out
+                        let bright = |c: u8| c / 2 + 128;
+                        texture.set_color_mod(bright(self.tint.0), bright(self.tint.1), bright(self.tint.2));
+                        canvas.copy(texture, src, Rect::new(p.x as i32, p.y as i32, w, h))?;
+                        texture.set_color_mod(self.tint.0, self.tint.1, self.tint.2);
+                    } else {
+                        canvas.copy(texture, src, Rect::new(p.x as i32, p.y as i32, w, h))?;
+                    }
+                }
+                ParticleShape::Square => {
+                    canvas.set_blend_mode(BlendMode::Blend);
+                    canvas.set_draw_color(Color::RGBA(color.0, color.1, color.2, alpha));
+                    let s = size.max(1.0) as u32;
+                    canvas.fill_rect(Rect::new(p.x as i32, p.y as i32, s, s))?;
+                    canvas.set_blend_mode(BlendMode::None);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Save for Particle {
+    fn save(&self, w: &mut SaveWriter) {
+        (self.x, self.y).save(w);
+        (self.vx, self.vy).save(w);
+        (self.age, self.lifetime).save(w);
+        (self.texture_idx, self.collectible).save(w);
+        self.dying.save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let (x, y) = Save::load(r)?;
+        let (vx, vy) = Save::load(r)?;
+        let (age, lifetime) = Save::load(r)?;
+        let (texture_idx, collectible) = Save::load(r)?;
+        Ok(Self { x, y, vx, vy, age, lifetime, texture_idx, dying: Save::load(r)?, collectible })
+    }
+}
+
+impl Save for EmitterState {
+    fn save(&self, w: &mut SaveWriter) {
+        self.particles.save(w);
+        self.accumulator.save(w);
+        self.rng.save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        Ok(Self { particles: Save::load(r)?, accumulator: Save::load(r)?, rng: Save::load(r)? })
+    }
+}
@@ -0,0 +1,86 @@
+use crate::config::Config;
+use crate::save::{load_variant, save_variant, Save, SaveReader, SaveWriter};
+
+/// How the player controls the sub
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ControlMode {
+    /// Every press of space gives the sub an upward push
+    Dive,
+    /// Holding space blows the ballast tanks and the sub rises, releasing it floods them and the sub sinks
+    Ballast,
+}
+
+const CONTROL_MODES: [ControlMode; 2] = [ControlMode::Dive, ControlMode::Ballast];
+
+/// Physics of the sub
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Physics {
+    pub control: ControlMode,
+    /// Downward acceleration per tick
+    pub gravity: f32,
+    /// Velocity after a dive (negative is up)
+    pub jump_strength: f32,
+    /// Maximum speed up or down
+    pub max_velocity: f32,
+    /// Upward acceleration per tick while the ballast tanks are blown
+    pub buoyancy: f32,
+    /// Fraction of the velocity that is kept each tick in ballast mode (1.0 is no drag)
+    pub drag: f32,
+}
+
+impl Physics {
+    /// Physics of the difficulty presets: easy, normal and hard
+    pub fn preset(difficulty: &str) -> Result<Self, String> {
+        let (gravity, jump_strength, max_velocity) = match difficulty {
+            "easy" => (0.15, -4.5, 8.0),
+            "normal" => (0.2, -5.0, 10.0),
+            "hard" => (0.28, -5.8, 12.0),
+            s => return Err(format!("unknown difficulty '{}'", s)),
+        };
+
+        Ok(Self {
+            control: ControlMode::Dive,
+            gravity,
+            jump_strength,
+            max_velocity,
+            buoyancy: gravity * 1.5,
+            drag: 0.97,
+        })
+    }
+
+    /// Reads the physics from the settings. Starts with a difficulty preset, of which every value can be overridden.
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let preset = Self::preset(&config.get_str("difficulty", "normal"))?;
+
+        let control = match config.get_str("control", "dive").as_str() {
+            "dive" => ControlMode::Dive,
+            "ballast" => ControlMode::Ballast,
+            s => return Err(format!("unknown control mode '{}'", s)),
+        };
+
+        Ok(Self {
+            control,
+            gravity: config.get_f32("gravity", preset.gravity)?,
+            jump_strength: config.get_f32("jump_strength", preset.jump_strength)?,
+            max_velocity: config.get_f32("max_velocity", preset.max_velocity)?,
+            buoyancy: config.get_f32("buoyancy", preset.buoyancy)?,
+            drag: config.get_f32("drag", preset.drag)?.clamp(0.0, 1.0),
+        })
+    }
+}
+
+impl Save for Physics {
+    fn save(&self, w: &mut SaveWriter) {
+        save_variant(&CONTROL_MODES, &self.control, w);
+        (self.gravity, self.jump_strength).save(w);
+        (self.max_velocity, self.buoyancy).save(w);
+        self.drag.save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let control = load_variant(&CONTROL_MODES, r)?;
+        let (gravity, jump_strength) = Save::load(r)?;
+        let (max_velocity, buoyancy) = Save::load(r)?;
+        Ok(Self { control, gravity, jump_strength, max_velocity, buoyancy, drag: Save::load(r)? })
+    }
+}
@@ -0,0 +1,107 @@
+use crate::events::GameEvent;
+use crate::save::{Save, SaveReader, SaveWriter};
+
+type Color = (u8, u8, u8);
+
+/// Power-ups that can be picked up during a run
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PowerUp {
+    /// Absorbs one collision
+    Shield,
+    /// Halves the speed of the game
+    SlowMotion,
+    /// Makes the sub (and its hitbox) smaller
+    Shrink,
+    /// Pulls collectibles towards the sub
+    Magnet,
+}
+
+pub const ALL_POWERUPS: [PowerUp; 4] = [
+    PowerUp::Shield,
+    PowerUp::SlowMotion,
+    PowerUp::Shrink,
+    PowerUp::Magnet,
+];
+
+impl PowerUp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PowerUp::Shield => "Shield",
+            PowerUp::SlowMotion => "Slow",
+            PowerUp::Shrink => "Shrink",
+            PowerUp::Magnet => "Magnet",
+        }
+    }
+
+    /// Number of ticks the power-up stays active
+    pub fn duration(&self) -> u32 {
+        match self {
+            PowerUp::Shield => 600,
+            PowerUp::SlowMotion => 300,
+            PowerUp::Shrink => 480,
+            PowerUp::Magnet => 480,
+        }
+    }
+
+    /// Color of the pickup, the effect on the sub and the timer in the HUD
+    pub fn color(&self) -> Color {
+        match self {
+            PowerUp::Shield => (120, 200, 255),
+            PowerUp::SlowMotion => (255, 220, 90),
+            PowerUp::Shrink => (140, 255, 140),
+            PowerUp::Magnet => (255, 110, 110),
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        ALL_POWERUPS.iter().position(|p| p == self).unwrap()
+    }
+}
+
+/// Timers of the power-ups that are active in the current run
+#[derive(Clone)]
+pub struct PowerUps {
+    timers: [u32; ALL_POWERUPS.len()],
+}
+
+impl PowerUps {
+    pub fn new() -> Self {
+        Self { timers: [0; ALL_POWERUPS.len()] }
+    }
+
+    pub fn active(&self, power_up: PowerUp) -> bool {
+        self.timers[power_up.index()] > 0
+    }
+
+    /// Ticks left for the power-up, 0 when not active
+    pub fn remaining(&self, power_up: PowerUp) -> u32 {
+        self.timers[power_up.index()]
+    }
+
+    /// Counts down all active power-ups
+    pub fn update(&mut self) {
+        for timer in self.timers.iter_mut() {
+            *timer = timer.saturating_sub(1);
+        }
+    }
+
+    pub fn on_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::RunStarted => self.timers = [0; ALL_POWERUPS.len()],
+            GameEvent::PickedUp { power_up } => self.timers[power_up.index()] = power_up.duration(),
+            GameEvent::ShieldAbsorbed => self.timers[PowerUp::Shield.index()] = 0,
+            _ => {}
+        }
+    }
+}
+
+impl Save for PowerUps {
+    fn save(&self, w: &mut SaveWriter) {
+        self.timers.to_vec().save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let timers = Vec::<u32>::load(r)?;
+        Ok(Self { timers: timers.try_into().map_err(|_| "invalid power-ups in save file")? })
+    }
+}
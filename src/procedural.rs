@@ -0,0 +1,55 @@
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Texture, TextureCreator};
+use sdl2::surface::Surface;
+use sdl2::video::WindowContext;
+
+/// Creates a texture by evaluating a shape function for every pixel. The function receives the
+/// pixel position normalized to 0.0 - 1.0 and returns the (brightness, alpha) of that pixel, or
+/// None when the pixel is transparent. Shapes are drawn in white-ish tones so they can be tinted
+/// with set_color_mod().
+pub fn shape_texture<'a, F>(
+    texture_creator: &'a TextureCreator<WindowContext>,
+    width: u32,
+    height: u32,
+    shape: F,
+) -> Result<Texture<'a>, String>
+where
+    F: Fn(f32, f32) -> Option<(u8, u8)>,
+{
+    let mut surface = Surface::new(width, height, PixelFormatEnum::RGBA32)?;
+    let pitch = surface.pitch() as usize;
+
+    surface.with_lock_mut(|pixels| {
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let u = (x as f32 + 0.5) / width as f32;
+                let v = (y as f32 + 0.5) / height as f32;
+
+                let (brightness, alpha) = shape(u, v).unwrap_or((0, 0));
+                let offset = y * pitch + x * 4;
+                pixels[offset..offset + 4].copy_from_slice(&[brightness, brightness, brightness, alpha]);
+            }
+        }
+    });
+
+    texture_creator
+        .create_texture_from_surface(&surface)
+        .map_err(|e| e.to_string())
+}
+
+/// Returns true when (u, v) is inside the ellipse with center (cx, cy) and radii (rx, ry)
+pub fn in_ellipse(u: f32, v: f32, cx: f32, cy: f32, rx: f32, ry: f32) -> bool {
+    let dx = (u - cx) / rx;
+    let dy = (v - cy) / ry;
+    dx * dx + dy * dy <= 1.0
+}
+
+/// Distance from (u, v) to the line segment (x1, y1) - (x2, y2)
+pub fn distance_to_segment(u: f32, v: f32, x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let len = dx * dx + dy * dy;
+    let t = if len == 0.0 { 0.0 } else { (((u - x1) * dx + (v - y1) * dy) / len).clamp(0.0, 1.0) };
+
+    let (px, py) = (x1 + t * dx, y1 + t * dy);
+    ((u - px) * (u - px) + (v - py) * (v - py)).sqrt()
+}
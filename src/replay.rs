@@ -0,0 +1,212 @@
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use crate::actors::sub::CONTROLS;
+use crate::mode::{GameMode, ALL_MODES};
+use crate::physics::Physics;
+use crate::save::{load_variant, save_variant, Save, SaveReader, SaveWriter};
+use crate::settings::Settings;
+
+/// Something the player did during a run
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Input {
+    /// Pressed the dive key
+    Dive,
+    /// Held the dive key long enough for the keyboard to repeat it
+    DiveRepeat,
+    /// Let go of the dive key
+    Release,
+    Fire,
+    /// Ended the run
+    End,
+}
+
+const ALL_INPUTS: [Input; 5] = [Input::Dive, Input::DiveRepeat, Input::Release, Input::Fire, Input::End];
+
+impl Input {
+    /// The input of the first player behind an event, if any
+    fn from_event(event: &Event) -> Option<Input> {
+        let (dive, fire) = CONTROLS[0];
+
+        match event {
+            Event::KeyDown { keycode: Some(key), repeat, .. } if *key == dive => {
+                Some(if *repeat { Input::DiveRepeat } else { Input::Dive })
+            }
+            Event::KeyUp { keycode: Some(key), .. } if *key == dive => Some(Input::Release),
+            Event::KeyDown { keycode: Some(key), repeat: false, .. } if *key == fire => Some(Input::Fire),
+            Event::KeyDown { keycode: Some(Keycode::C), .. } => Some(Input::End),
+            _ => None,
+        }
+    }
+
+    /// The event the actors see for this input. Ending the run is not up to the actors, so it has no event.
+    pub fn event(&self) -> Option<Event> {
+        let (dive, fire) = CONTROLS[0];
+        let key_down = |keycode, repeat| Event::KeyDown {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(keycode),
+            scancode: None,
+            keymod: Mod::NOMOD,
+            repeat,
+        };
+
+        match self {
+            Input::Dive => Some(key_down(dive, false)),
+            Input::DiveRepeat => Some(key_down(dive, true)),
+            Input::Release => Some(Event::KeyUp {
+                timestamp: 0,
+                window_id: 0,
+                keycode: Some(dive),
+                scancode: None,
+                keymod: Mod::NOMOD,
+                repeat: false,
+            }),
+            Input::Fire => Some(key_down(fire, false)),
+            Input::End => None,
+        }
+    }
+}
+
+impl Save for Input {
+    fn save(&self, w: &mut SaveWriter) {
+        save_variant(&ALL_INPUTS, self, w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        load_variant(&ALL_INPUTS, r)
+    }
+}
+
+/// Everything needed to play a run again: the seed and the mode the run started with, and every input of the player
+/// since. Everything else in a run follows from the seeded rngs, so replaying the inputs ends in the same score. This
+/// is how the leaderboard server checks the scores sent to it.
+pub struct Replay {
+    /// Name of the player on the leaderboard
+    pub name: String,
+    /// Score the run ended with
+    pub score: i64,
+    pub seed: u64,
+    pub mode: GameMode,
+    /// Settings the run was played with. The leaderboard only takes runs played with its own settings.
+    pub physics: Physics,
+    pub terrain_collision: bool,
+    /// Inputs and the frames they happened at, in order
+    inputs: Vec<(i64, Input)>,
+}
+
+impl Replay {
+    /// Starts recording a ranked run, right after it started
+    pub fn new(settings: &Settings, seed: u64, mode: GameMode) -> Self {
+        // Names are a single word in the replay
+        let name = settings.player_name.split_whitespace().collect::<Vec<_>>().join("_");
+
+        Self {
+            name: if name.is_empty() { "diver".to_string() } else { name },
+            score: 0,
+            seed,
+            mode,
+            physics: settings.physics,
+            terrain_collision: settings.terrain_collision,
+            inputs: vec![],
+        }
+    }
+
+    /// Records an event at the given frame, when it is an input that matters for the run
+    pub fn record(&mut self, fc: i64, event: &Event) {
+        if let Some(input) = Input::from_event(event) {
+            self.inputs.push((fc, input));
+        }
+    }
+
+    pub fn inputs(&self) -> &[(i64, Input)] {
+        &self.inputs
+    }
+
+    /// The replay as a single line of text
+    pub fn encode(&self) -> String {
+        let mut w = SaveWriter::new();
+        w.label("replay");
+        (self.name.clone(), self.score).save(&mut w);
+        self.seed.save(&mut w);
+        save_variant(&ALL_MODES, &self.mode, &mut w);
+        self.physics.save(&mut w);
+        self.terrain_collision.save(&mut w);
+        w.label("inputs");
+        self.inputs.save(&mut w);
+
+        w.into_line() + "\n"
+    }
+
+    pub fn decode(s: &str) -> Result<Self, String> {
+        let mut r = SaveReader::new(s)?;
+        r.expect("replay")?;
+        let (name, score) = Save::load(&mut r)?;
+        let seed = Save::load(&mut r)?;
+        let mode = load_variant(&ALL_MODES, &mut r)?;
+        let physics = Save::load(&mut r)?;
+        let terrain_collision = Save::load(&mut r)?;
+        r.expect("inputs")?;
+        let inputs = Save::load(&mut r)?;
+
+        Ok(Self { name, score, seed, mode, physics, terrain_collision, inputs })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded() -> Replay {
+        let settings = Settings::load("no-such-settings-file.cfg").unwrap();
+        let mut replay = Replay::new(&settings, 1234, GameMode::Classic);
+
+        for (fc, input) in [(10, Input::Dive), (11, Input::DiveRepeat), (30, Input::Release), (31, Input::Fire)] {
+            replay.record(fc, &input.event().unwrap());
+        }
+        let mut end = Input::Fire.event().unwrap();
+        if let Event::KeyDown { keycode, .. } = &mut end {
+            *keycode = Some(Keycode::C);
+        }
+        replay.record(400, &end);
+        replay.score = 42;
+
+        replay
+    }
+
+    #[test]
+    fn only_inputs_of_the_first_player_are_recorded() {
+        let mut replay = recorded();
+        let (_, fire) = CONTROLS[1];
+        let mut other = Input::Fire.event().unwrap();
+        if let Event::KeyDown { keycode, .. } = &mut other {
+            *keycode = Some(fire);
+        }
+        replay.record(500, &other);
+
+        assert_eq!(replay.inputs().len(), 5);
+        assert_eq!(replay.inputs()[4], (400, Input::End));
+    }
+
+    #[test]
+    fn encoded_replay_decodes_to_the_same_run() {
+        let replay = recorded();
+        let decoded = Replay::decode(&replay.encode()).unwrap();
+
+        assert_eq!(decoded.name, replay.name);
+        assert_eq!(decoded.score, 42);
+        assert_eq!(decoded.seed, 1234);
+        assert_eq!(decoded.mode, GameMode::Classic);
+        assert_eq!(decoded.physics, replay.physics);
+        assert_eq!(decoded.terrain_collision, replay.terrain_collision);
+        assert_eq!(decoded.inputs(), replay.inputs());
+    }
+
+    #[test]
+    fn broken_replays_are_rejected() {
+        let encoded = recorded().encode();
+
+        assert!(Replay::decode("").is_err());
+        assert!(Replay::decode(&encoded[..encoded.len() / 2]).is_err());
+        assert!(Replay::decode(&encoded.replace("replay", "run")).is_err());
+    }
+}
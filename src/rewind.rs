@@ -0,0 +1,139 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use crate::{GameState, MAX_PLAYERS};
+use crate::actors::Actors;
+use crate::events::GameEvent;
+use crate::oxygen::Oxygen;
+use crate::powerup::PowerUps;
+use crate::rng::GameRng;
+
+/// Number of frames kept in the history, so the player can rewind at most this far
+const HISTORY_FRAMES: usize = 300;
+
+/// Number of frames the player can rewind in a single run
+const REWIND_BUDGET: u32 = 600;
+
+/// Frames the player has to start rewinding after a crash
+pub const REWIND_PROMPT: u32 = 120;
+
+/// The whole simulation at a single frame
+pub struct Snapshot {
+    actors: Vec<Option<Box<dyn Any>>>,
+    fc: i64,
+    bonus: i64,
+    torpedoes: u32,
+    pipes: u32,
+    distance: i64,
+    sunk_at: [Option<i64>; MAX_PLAYERS],
+    power_ups: PowerUps,
+    oxygen: Oxygen,
+    rng: GameRng,
+}
+
+impl Snapshot {
+    pub fn take(state: &GameState, actors: &Actors) -> Self {
+        Self {
+            actors: actors.snapshot(),
+            fc: state.fc,
+            bonus: state.bonus,
+            torpedoes: state.torpedoes,
+            pipes: state.pipes,
+            distance: state.distance,
+            sunk_at: state.sunk_at,
+            power_ups: state.power_ups.clone(),
+            oxygen: state.oxygen.clone(),
+            rng: state.rng().clone(),
+        }
+    }
+
+    pub fn restore(self, state: &mut GameState, actors: &mut Actors) {
+        actors.restore(self.actors);
+
+        state.fc = self.fc;
+        state.bonus = self.bonus;
+        state.torpedoes = self.torpedoes;
+        state.pipes = self.pipes;
+        state.distance = self.distance;
+        state.sunk_at = self.sunk_at;
+        state.power_ups = self.power_ups;
+        state.oxygen = self.oxygen;
+        *state.rng() = self.rng;
+        state.player = actors.player_position();
+    }
+}
+
+/// What the rewind is doing at the moment
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RewindMode {
+    /// Playing normally
+    Off,
+    /// Crashed, waiting for the player to start rewinding
+    Prompt,
+    /// Going back in time
+    Rewinding,
+}
+
+/// History of the last frames of the run, and how much of it the player may still rewind
+pub struct Rewind {
+    history: VecDeque<Snapshot>,
+    budget: u32,
+    pub mode: RewindMode,
+}
+
+impl Rewind {
+    pub fn new() -> Self {
+        Self {
+            history: VecDeque::with_capacity(HISTORY_FRAMES),
+            budget: REWIND_BUDGET,
+            mode: RewindMode::Off,
+        }
+    }
+
+    /// Frames that can still be rewound in this run
+    pub fn budget(&self) -> u32 {
+        self.budget
+    }
+
+    /// True when there is anything to rewind to
+    pub fn available(&self) -> bool {
+        self.budget > 0 && !self.history.is_empty()
+    }
+
+    /// Adds a frame to the history, forgetting the oldest frame when the history is full
+    pub fn record(&mut self, snapshot: Snapshot) {
+        if self.history.len() == HISTORY_FRAMES {
+            self.history.pop_front();
+        }
+        self.history.push_back(snapshot);
+    }
+
+    /// Takes the most recent frame from the history, as long as there is budget left
+    pub fn pop(&mut self) -> Option<Snapshot> {
+        if self.budget == 0 {
+            return None;
+        }
+
+        let snapshot = self.history.pop_back()?;
+        self.budget -= 1;
+        Some(snapshot)
+    }
+
+    /// Continues a saved run with the budget it had left. The history is not saved, so it starts out empty.
+    pub fn resume(&mut self, budget: u32) {
+        self.history.clear();
+        self.budget = budget;
+        self.mode = RewindMode::Off;
+    }
+
+    pub fn on_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::RunStarted => {
+                self.history.clear();
+                self.budget = REWIND_BUDGET;
+            }
+            // The ocean was rebuilt, so the history no longer matches
+            GameEvent::Respawned { .. } => self.history.clear(),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use rand::RngCore;
+use crate::save::{Save, SaveReader, SaveWriter};
+
+/// Random number generator for pipes, enemies, pickups and currents. The sand dunes and the air pockets are not drawn
+/// from it, but are reseeded from the same seed at the start of a run, so runs with the same seed generate the same
+/// ocean. Purely cosmetic randomness (fauna, parallax) uses the thread rng instead, so it does not disturb the
+/// sequence.
+///
+/// This is splitmix64, which keeps its whole state in a single number.
+#[derive(Clone)]
+pub struct GameRng {
+    state: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Starts the sequence over with the given seed
+    pub fn reseed(&mut self, seed: u64) {
+        self.state = seed;
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        for chunk in dst.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+impl Save for GameRng {
+    fn save(&self, w: &mut SaveWriter) {
+        self.state.save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        Ok(Self { state: Save::load(r)? })
+    }
+}
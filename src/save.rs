@@ -0,0 +1,185 @@
+use std::fs;
+use std::str::SplitWhitespace;
+use sdl2::rect::Rect;
+
+/// File in which a run is saved when the player quits halfway
+pub const SAVE_FILE: &str = "flappydive.sav";
+
+/// First word of every save file, followed by the version of the format
+const MAGIC: &str = "flappydive-save";
+const VERSION: u32 = 1;
+
+/// Values are written as words separated by whitespace. Labels between the sections make sure a broken or outdated
+/// save file is detected, instead of loading garbage.
+pub struct SaveWriter {
+    out: String,
+}
+
+impl SaveWriter {
+    pub fn new() -> Self {
+        let mut w = Self { out: String::new() };
+        w.label(MAGIC);
+        VERSION.save(&mut w);
+        w
+    }
+
+    /// Starts a new section, on its own line
+    pub fn label(&mut self, label: &str) {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.word(label);
+    }
+
+    fn word(&mut self, word: &str) {
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push(' ');
+        }
+        self.out.push_str(word);
+    }
+
+    /// Everything written so far as a single line, to send it over the network
+    pub fn into_line(self) -> String {
+        self.out.replace('\n', " ")
+    }
+
+    pub fn write(self, path: &str) -> Result<(), String> {
+        fs::write(path, self.out + "\n").map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+pub struct SaveReader<'a> {
+    words: SplitWhitespace<'a>,
+}
+
+impl<'a> SaveReader<'a> {
+    pub fn new(contents: &'a str) -> Result<Self, String> {
+        let mut r = Self { words: contents.split_whitespace() };
+        r.expect(MAGIC)?;
+        if u32::load(&mut r)? != VERSION {
+            return Err("save file is from another version of the game".into());
+        }
+
+        Ok(r)
+    }
+
+    /// Reads the label of the next section
+    pub fn label(&mut self) -> Result<&'a str, String> {
+        self.word()
+    }
+
+    /// Checks that the next section is the one we expect
+    pub fn expect(&mut self, label: &str) -> Result<(), String> {
+        match self.word()? {
+            word if word == label => Ok(()),
+            word => Err(format!("expected '{}' in save file, found '{}'", label, word)),
+        }
+    }
+
+    fn word(&mut self) -> Result<&'a str, String> {
+        self.words.next().ok_or_else(|| "save file ends too soon".to_string())
+    }
+}
+
+/// Something that can be written to and read from a save file
+pub trait Save: Sized {
+    fn save(&self, w: &mut SaveWriter);
+    fn load(r: &mut SaveReader) -> Result<Self, String>;
+}
+
+macro_rules! save_by_display {
+    ($($t:ty),*) => {$(
+        impl Save for $t {
+            fn save(&self, w: &mut SaveWriter) {
+                w.word(&self.to_string());
+            }
+
+            fn load(r: &mut SaveReader) -> Result<Self, String> {
+                let word = r.word()?;
+                word.parse().map_err(|_| format!("invalid value '{}' in save file", word))
+            }
+        }
+    )*};
+}
+
+save_by_display!(bool, u32, u64, usize, i32, i64, f32);
+
+/// Strings are saved as a single word, so they should not contain any whitespace
+impl Save for String {
+    fn save(&self, w: &mut SaveWriter) {
+        w.word(self);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        Ok(r.word()?.to_string())
+    }
+}
+
+impl Save for () {
+    fn save(&self, _w: &mut SaveWriter) {}
+
+    fn load(_r: &mut SaveReader) -> Result<Self, String> {
+        Ok(())
+    }
+}
+
+impl<T: Save> Save for Option<T> {
+    fn save(&self, w: &mut SaveWriter) {
+        self.is_some().save(w);
+        if let Some(value) = self {
+            value.save(w);
+        }
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        if bool::load(r)? { Ok(Some(T::load(r)?)) } else { Ok(None) }
+    }
+}
+
+impl<T: Save> Save for Vec<T> {
+    fn save(&self, w: &mut SaveWriter) {
+        self.len().save(w);
+        for value in self {
+            value.save(w);
+        }
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let len = usize::load(r)?;
+        (0..len).map(|_| T::load(r)).collect()
+    }
+}
+
+impl<A: Save, B: Save> Save for (A, B) {
+    fn save(&self, w: &mut SaveWriter) {
+        self.0.save(w);
+        self.1.save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        Ok((A::load(r)?, B::load(r)?))
+    }
+}
+
+impl Save for Rect {
+    fn save(&self, w: &mut SaveWriter) {
+        (self.x(), self.y()).save(w);
+        (self.width(), self.height()).save(w);
+    }
+
+    fn load(r: &mut SaveReader) -> Result<Self, String> {
+        let (x, y) = <(i32, i32)>::load(r)?;
+        let (width, height) = <(u32, u32)>::load(r)?;
+        Ok(Rect::new(x, y, width, height))
+    }
+}
+
+/// Saves an enum by its position in the list of all variants
+pub fn save_variant<T: PartialEq>(all: &[T], value: &T, w: &mut SaveWriter) {
+    all.iter().position(|v| v == value).unwrap_or(0).save(w);
+}
+
+pub fn load_variant<T: Copy>(all: &[T], r: &mut SaveReader) -> Result<T, String> {
+    let idx = usize::load(r)?;
+    all.get(idx).copied().ok_or_else(|| format!("invalid variant {} in save file", idx))
+}
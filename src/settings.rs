@@ -0,0 +1,47 @@
+use std::path::Path;
+use crate::config::Config;
+use crate::physics::Physics;
+
+/// File with the user settings. When it does not exist, the defaults are used.
+pub const SETTINGS_FILE: &str = "flappydive.cfg";
+
+/// Game settings as found in the settings file
+pub struct Settings {
+    /// When true, the sub crashes into the dunes instead of diving into the sand
+    pub terrain_collision: bool,
+    /// Physics of the sub, based on the difficulty
+    pub physics: Physics,
+    /// Fixed seed for every run, so every run has the same ocean. Otherwise every run gets a random seed.
+    pub seed: Option<u64>,
+    /// Port to host network races on
+    pub race_port: u16,
+    /// Address ("host:port") of the race to join
+    pub race_host: String,
+    /// Address of the leaderboard server ("http://host:port")
+    pub leaderboard_url: String,
+    /// Name on the leaderboard
+    pub player_name: String,
+}
+
+impl Settings {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let config = if Path::new(path).exists() {
+            Config::load(path)?
+        } else {
+            Config::parse(path, "")?
+        };
+
+        Ok(Self {
+            terrain_collision: config.get_bool("terrain_collision", true)?,
+            physics: Physics::from_config(&config)?,
+            seed: config.get("seed")
+                .map(|s| s.parse().map_err(|_| format!("{}: 'seed' should be a number", path)))
+                .transpose()?,
+            race_port: u16::try_from(config.get_i32("race_port", 7777)?)
+                .map_err(|_| format!("{}: 'race_port' should be a port number", path))?,
+            race_host: config.get_str("race_host", "127.0.0.1:7777"),
+            leaderboard_url: config.get_str("leaderboard_url", "http://127.0.0.1:8080"),
+            player_name: config.get_str("player_name", "diver"),
+        })
+    }
+}
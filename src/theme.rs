@@ -1,12 +1,16 @@
+use crate::biome::{Biome, BLEND_FRAMES};
+
 type Color = (u8, u8, u8);
 
-#[allow(dead_code)]
+#[derive(Clone, Copy)]
 pub struct Theme {
     pub sand: Color,                // Sand color at the bottom
     pub sand_highlight: Color,      // Highlighted sand color
     pub water: Color,               // Water / ocean color
     pub pipes: Color,               // Color of the pipes
     pub bubbles: Color,             // Color of the water bubbles
+    pub rocks: Color,               // Distant rock silhouettes
+    pub kelp: Color,                // Mid-ground kelp
     pub   text: Color,                // Text on the screen
     pub sub: Color,                 // Submarine color
 
@@ -22,6 +26,8 @@ pub const COLOR_THEME: Theme = Theme {
     water: (74, 179, 219),
     pipes: (111, 191, 115),
     bubbles: (136, 207, 241),
+    rocks: (45, 120, 160),
+    kelp: (60, 150, 90),
 
     text: (116, 100, 076),
     sub: (128, 128, 255),
@@ -38,6 +44,8 @@ pub const GRAYSCALE_THEME: Theme = Theme {
     water: (142, 142, 142),         // Grayscale of (74, 179, 219)
     pipes: (161, 161, 161),         // Grayscale of (111, 191, 115)
     bubbles: (196, 196, 196),       // Grayscale of (136, 207, 241)
+    rocks: (102, 102, 102),         // Grayscale of (45, 120, 160)
+    kelp: (116, 116, 116),          // Grayscale of (60, 150, 90)
 
     text: (104, 104, 104),          // Grayscale of (116, 100, 76)
     sub: (128, 128, 128),
@@ -54,6 +62,8 @@ pub const THEME: Theme = Theme {
     water: (93, 188, 210),          // Light cyan blue for water
     pipes: (129, 199, 132),         // Fresh mint green for pipes
     bubbles: (171, 222, 239),       // Soft pale blue for bubbles
+    rocks: (62, 140, 168),          // Muted deep blue for distant rocks
+    kelp: (76, 153, 97),            // Leafy green for kelp
 
     text: (89, 80, 66),             // Warm brown for readable text
     sub: (128, 128, 255),
@@ -70,6 +80,8 @@ pub const THEME_2: Theme = Theme {
     water: (89, 168, 245),          // Bright sky blue for water
     pipes: (84, 153, 124),          // Teal green for pipes
     bubbles: (156, 209, 247),       // Light blue for bubbles
+    rocks: (58, 118, 186),          // Dusky blue for distant rocks
+    kelp: (56, 142, 110),           // Sea green for kelp
 
     text: (70, 63, 55),             // Deep taupe for readable text
     sub: (128, 128, 255),
@@ -80,25 +92,148 @@ pub const THEME_2: Theme = Theme {
     fauna_color_4: (128, 90, 213),  // Bold violet for fauna or shells
 };
 
+fn blend_color(a: Color, b: Color, t: f32) -> Color {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    (mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
+}
+
+impl Theme {
+    /// Returns a theme where every color is blended with the other theme by t (0.0 - 1.0)
+    pub fn blend(&self, other: &Theme, t: f32) -> Theme {
+        Theme {
+            sand: blend_color(self.sand, other.sand, t),
+            sand_highlight: blend_color(self.sand_highlight, other.sand_highlight, t),
+            water: blend_color(self.water, other.water, t),
+            pipes: blend_color(self.pipes, other.pipes, t),
+            bubbles: blend_color(self.bubbles, other.bubbles, t),
+            rocks: blend_color(self.rocks, other.rocks, t),
+            kelp: blend_color(self.kelp, other.kelp, t),
+            text: blend_color(self.text, other.text, t),
+            sub: blend_color(self.sub, other.sub, t),
+            fauna_color_1: blend_color(self.fauna_color_1, other.fauna_color_1, t),
+            fauna_color_2: blend_color(self.fauna_color_2, other.fauna_color_2, t),
+            fauna_color_3: blend_color(self.fauna_color_3, other.fauna_color_3, t),
+            fauna_color_4: blend_color(self.fauna_color_4, other.fauna_color_4, t),
+        }
+    }
+
+    /// Returns the fauna color for the given slot (1-4)
+    pub fn fauna_color(&self, slot: usize) -> Color {
+        match slot {
+            1 => self.fauna_color_1,
+            2 => self.fauna_color_2,
+            3 => self.fauna_color_3,
+            _ => self.fauna_color_4,
+        }
+    }
+
+    /// Returns the theme as it looks inside the given biome. Text stays untouched so it's always readable.
+    fn shaded(&self, biome: &Biome) -> Theme {
+        let shade = Theme {
+            sand: biome.shade,
+            sand_highlight: biome.shade,
+            water: biome.shade,
+            pipes: biome.shade,
+            bubbles: biome.shade,
+            rocks: biome.shade,
+            kelp: biome.shade,
+            text: self.text,
+            sub: biome.shade,
+            fauna_color_1: biome.shade,
+            fauna_color_2: biome.shade,
+            fauna_color_3: biome.shade,
+            fauna_color_4: biome.shade,
+        };
+
+        self.blend(&shade, biome.shade_amount)
+    }
+}
+
+/// What changed after moving on through the biomes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ThemeChange {
+    /// Same biome, same colors
+    Unchanged,
+    /// The colors are blending into those of the next biome
+    Blending,
+    /// The sub entered another biome
+    NewBiome,
+}
+
 pub struct ThemeSwitcher {
     current_theme_idx: usize,
     themes: Vec<Theme>,
+    biomes: Vec<Biome>,
+    current_biome_idx: usize,
+    // Blend between the current and next biome (0.0 - 1.0)
+    blend: f32,
+    // The actual theme as it is displayed, based on theme, biome and blend
+    blended: Theme,
 }
 
 impl ThemeSwitcher {
-    pub(crate) fn new() -> Self {
-        Self {
+    pub(crate) fn new(biomes: Vec<Biome>) -> Self {
+        let mut switcher = Self {
             current_theme_idx: 0,
             themes: vec![COLOR_THEME, GRAYSCALE_THEME, THEME, THEME_2],
-        }
+            biomes,
+            current_biome_idx: 0,
+            blend: 0.0,
+            blended: COLOR_THEME,
+        };
+        switcher.recalculate();
+
+        switcher
     }
 
     pub(crate) fn next(&mut self) -> &Theme {
         self.current_theme_idx = (self.current_theme_idx + 1) % self.themes.len();
-        &self.themes[self.current_theme_idx]
+        self.recalculate();
+        &self.blended
     }
 
     pub(crate) fn current(&self) -> &Theme {
-        &self.themes[self.current_theme_idx]
+        &self.blended
+    }
+
+    /// Returns the biome the sub is currently in
+    pub(crate) fn biome(&self) -> &Biome {
+        &self.biomes[self.current_biome_idx]
+    }
+
+    /// Moves through the biomes based on the frame counter. Returns what changed, if anything.
+    pub(crate) fn update(&mut self, fc: i64) -> ThemeChange {
+        let idx = self.biomes.iter().rposition(|b| b.start <= fc).unwrap_or(0);
+
+        let blend = match self.biomes.get(idx + 1) {
+            Some(next) if fc > next.start - BLEND_FRAMES => {
+                (fc - (next.start - BLEND_FRAMES)) as f32 / BLEND_FRAMES as f32
+            }
+            _ => 0.0,
+        };
+
+        let change = if idx != self.current_biome_idx {
+            ThemeChange::NewBiome
+        } else if blend != self.blend {
+            ThemeChange::Blending
+        } else {
+            return ThemeChange::Unchanged;
+        };
+
+        self.current_biome_idx = idx;
+        self.blend = blend;
+        self.recalculate();
+
+        change
+    }
+
+    fn recalculate(&mut self) {
+        let theme = &self.themes[self.current_theme_idx];
+
+        let current = theme.shaded(&self.biomes[self.current_biome_idx]);
+        self.blended = match self.biomes.get(self.current_biome_idx + 1) {
+            Some(next) if self.blend > 0.0 => current.blend(&theme.shaded(next), self.blend),
+            _ => current,
+        };
     }
 }
\ No newline at end of file